@@ -1,34 +1,528 @@
+use std::collections::HashSet;
 use std::env;
+use std::error::Error;
+use std::io::{self, BufRead, IsTerminal};
 
+use crate::config::Config;
+
+/// Options controlling a run, normally parsed from the command line.
+///
+/// Library users can also build one directly, starting from
+/// `CliArgs::default()`.
+#[derive(Default)]
 pub struct CliArgs {
+    /// Descend into subdirectories when matching glob patterns (`-r`).
     pub recursive: bool,
+    /// Include files excluded by `.gitignore` (`-i`).
     pub ignore_gitignore: bool,
+    /// Subdirectories where `.gitignore` rules are not applied
+    /// (`--ignore-gitignore-in`).
+    pub ignore_gitignore_in: Vec<String>,
+    /// Extra gitignore-syntax patterns to exclude (`--ignore-pattern`).
+    pub ignore_patterns: Vec<String>,
+    /// Lowercased extensions (without the dot) files must have, including
+    /// files named explicitly; empty allows any extension.
+    pub include_extensions: HashSet<String>,
+    /// Lowercased extensions (without the dot) of files to skip.
+    pub exclude_extensions: HashSet<String>,
+    /// Only include files that `.gitignore` excludes (`--include-gitignored`).
+    pub include_gitignored: bool,
+    /// Match glob patterns case-insensitively.
+    pub icase: bool,
+    /// Only match glob patterns from the working directory root.
+    pub anchor_patterns: bool,
+    /// Match glob patterns against absolute paths rather than file names
+    /// (`--match-full-path`).
+    pub match_full_path: bool,
+    /// Additional working directories whose files are aggregated too (`--dir`).
+    pub dirs: Vec<String>,
+    /// Only output files changed in the most recent git stash.
+    pub git_stash: bool,
+    /// Only output files with uncommitted changes, untracked files included.
+    pub keep_only_changed: bool,
+    /// Only output files git doesn't track yet (`--git-untracked-only`).
+    pub git_untracked_only: bool,
+    /// Output deleted files with their contents from `HEAD`, narrowed by
+    /// the positional patterns if any (`--git-deleted-only`).
+    pub git_deleted_only: bool,
+    /// Only output files changed by commits after this ref (`--git-since-ref`).
+    pub git_since_ref: Option<String>,
+    /// Only output files from commits whose author matches (`--git-author`).
+    pub git_author: Option<String>,
+    /// Only output files from commits whose message matches (`--git-message-grep`).
+    pub git_message_grep: Option<String>,
+    /// Stop collecting once this many files matched (`--max-file-count`).
+    pub max_file_count: Option<usize>,
+    /// Check walked entries on a thread pool (`--parallel-collect`).
+    pub parallel_collect: bool,
+    /// Don't descend into other filesystems (`--one-file-system`).
+    pub one_file_system: bool,
+    /// Globs that at least one collected file must match (`--require-pattern`).
+    pub require_patterns: Vec<String>,
+    /// File of NUL- or newline-separated paths to aggregate, `-` for stdin
+    /// (`--file-list`).
+    pub file_list: Option<String>,
+    /// Paths, directories or glob patterns to aggregate.
     pub patterns: Vec<String>,
+    /// GitHub URL to download and process instead of the local tree.
     pub github_url: Option<String>,
+    /// Host of the GitHub (Enterprise Server) instance; defaults to the URL's host.
+    pub github_host: Option<String>,
+    /// `X-GitHub-Api-Version` sent with every request; `2022-11-28` when unset.
+    pub github_api_version: Option<String>,
+    /// Branch to download, overriding any `/tree/<branch>` in the URL.
+    pub branch: Option<String>,
+    /// Commit SHA to download instead of the branch tip (`--git-commit`).
+    pub git_commit: Option<String>,
+    /// Subdirectory of the repository to process, overriding the URL's path.
+    pub path: Option<String>,
+    /// Retries for transient GitHub download failures; 3 when unset.
+    pub retry: Option<u32>,
+    /// Delay between download retries in milliseconds; 1000 when unset.
+    pub retry_delay: Option<u64>,
+    /// Show a progress bar while downloading from GitHub.
+    pub progress: bool,
+    /// Print version information and exit.
     pub show_version: bool,
+    /// Print the cache location, repository count and size, then exit.
+    pub cache_info: bool,
+    /// Regexes whose matches are masked in file contents.
+    pub redact_patterns: Vec<String>,
+    /// Text substituted for redacted matches; `<REDACTED>` when unset.
+    pub redact_replacement: Option<String>,
+    /// Language whose comments are stripped from file contents.
+    pub strip_comments: Option<String>,
+    /// Only include files whose contents match this regex.
+    pub search: Option<String>,
+    /// With `search`, only output matching lines plus this much context.
+    pub context_lines: Option<usize>,
+    /// Report estimated token counts instead of printing contents.
+    pub count_tokens: bool,
+    /// Print per-file size, line and token statistics instead of contents.
+    pub stats_only: bool,
+    /// Append a SHA-256 of each file's raw bytes to its header.
+    pub sha256: bool,
+    /// Write an `agg-manifest.json` describing the run (`--manifest`).
+    pub manifest: bool,
+    /// Show where symlinked files point in their headers.
+    pub symlink_target: bool,
+    /// Include files even if they look binary (`--no-binary-check`).
+    pub no_binary_check: bool,
+    /// Strip trailing line breaks from file contents (`--no-newline-at-eof`).
+    pub no_newline_at_eof: bool,
+    /// Convert CRLF and bare CR line endings to LF (`--normalise-line-endings`).
+    pub normalise_line_endings: bool,
+    /// Start each file with a comment naming its language
+    /// (`--add-language-comment`).
+    pub add_language_comment: bool,
+    /// Copy the output to the system clipboard instead of printing it.
+    pub clipboard: bool,
+    /// Shell command whose stdin receives the output (`--pipe-to`).
+    pub pipe_to: Option<String>,
+    /// Earlier output to print a unified diff against (`--compare`).
+    pub compare: Option<String>,
+    /// Shell command run after a successful run (`--on-success`).
+    pub on_success: Option<String>,
+    /// Shell command run after a failed run (`--on-failure`).
+    pub on_failure: Option<String>,
+    /// `KEY=VALUE` pairs added to the hooks' environment (`--env`).
+    pub hook_env: Vec<String>,
+    /// Base directory that output paths are shown relative to (`--relative-to`).
+    pub relative_to: Option<String>,
+    /// Hard-wrap output lines longer than this many characters (`--wrap`).
+    pub wrap: Option<usize>,
+    /// Encoding used for files that aren't valid UTF-8 (`--encoding-fallback`);
+    /// unset or `none` reports them as unreadable.
+    pub encoding_fallback: Option<String>,
+    /// Output format name (`text`, `ndjson`, `html`, `org`, `rst`); text when
+    /// unset.
+    pub format: Option<String>,
+    /// Compression for stdout or `--pipe-to` output (`gzip`, `zstd`, `none`).
+    pub compress: Option<String>,
+    /// Per-file header template; `# File: {path}` when unset.
+    pub file_header_template: Option<String>,
+    /// Per-file footer template; a `=====` separator when unset.
+    pub file_footer_template: Option<String>,
+    /// Separate files with a NUL byte instead of the footer (stdout only).
+    pub emit_null_separator: bool,
+    /// Log every included and skipped file to stderr.
+    pub verbose: bool,
+    /// Suppress status messages; errors are still reported.
+    pub quiet: bool,
+    /// Most verbose log level to print (`--log-level`); derived from
+    /// `--verbose`/`--quiet` when unset.
+    pub log_level: Option<String>,
+    /// Log output format (`--log-format`): `text` (default) or `json`.
+    pub log_format: Option<String>,
+    /// Only print the first N characters of the output, plus a summary.
+    pub preview: Option<usize>,
+    /// Run the whole pipeline this many times and print timing statistics
+    /// instead of the output (`--benchmark`).
+    pub benchmark: Option<usize>,
 }
 
 impl CliArgs {
-    pub fn parse() -> Self {
-        let args: Vec<String> = env::args().collect();
+    /// Parses the process arguments.
+    ///
+    /// Fails if the `--profile` named on the command line can't be loaded.
+    pub fn parse() -> Result<Self, Box<dyn Error>> {
+        let mut args: Vec<String> = env::args().collect();
+
+        // Profile settings go first so anything on the command line wins
+        if let Some(name) = Self::profile_name(&args) {
+            args.splice(1..1, Config::load_profile(&name)?);
+        }
+
         let mut recursive = false;
         let mut ignore_gitignore = false;
+        let mut ignore_gitignore_in = Vec::new();
+        let mut ignore_patterns = Vec::new();
+        let mut include_extensions = HashSet::new();
+        let mut exclude_extensions = HashSet::new();
+        let mut include_gitignored = false;
+        let mut icase = false;
+        let mut anchor_patterns = false;
+        let mut match_full_path = false;
+        let mut max_file_count = None;
+        let mut parallel_collect = false;
+        let mut one_file_system = false;
+        let mut git_stash = false;
+        let mut keep_only_changed = false;
+        let mut git_untracked_only = false;
+        let mut git_deleted_only = false;
+        let mut git_since_ref = None;
+        let mut git_author = None;
+        let mut git_message_grep = None;
+        let mut dirs = Vec::new();
+        let mut require_patterns = Vec::new();
+        let mut file_list = None;
         let mut patterns = Vec::new();
         let mut github_url = None;
+        let mut github_host = None;
+        let mut github_api_version = None;
+        let mut branch = None;
+        let mut git_commit = None;
+        let mut path = None;
+        let mut retry = None;
+        let mut retry_delay = None;
+        let mut progress = false;
         let mut show_version = false;
+        let mut cache_info = false;
+        let mut redact_patterns = Vec::new();
+        let mut redact_replacement = None;
+        let mut strip_comments = None;
+        let mut search = None;
+        let mut context_lines = None;
+        let mut count_tokens = false;
+        let mut stats_only = false;
+        let mut sha256 = false;
+        let mut manifest = false;
+        let mut symlink_target = false;
+        let mut no_binary_check = false;
+        let mut no_newline_at_eof = false;
+        let mut normalise_line_endings = false;
+        let mut add_language_comment = false;
+        let mut clipboard = false;
+        let mut stdin_patterns = false;
+        let mut preview = None;
+        let mut benchmark = None;
+        let mut relative_to = None;
+        let mut pipe_to = None;
+        let mut compare = None;
+        let mut on_success = None;
+        let mut on_failure = None;
+        let mut hook_env = Vec::new();
+        let mut wrap = None;
+        let mut encoding_fallback = None;
+        let mut log_level = None;
+        let mut log_format = None;
+        let mut format = None;
+        let mut compress = None;
+        let mut file_header_template = None;
+        let mut file_footer_template = None;
+        let mut emit_null_separator = false;
+        let mut verbose = false;
+        let mut quiet = false;
         let mut i = 1;
 
         while i < args.len() {
             match args[i].as_str() {
-                "-r" => recursive = true,
-                "-i" => ignore_gitignore = true,
+                "-r" | "--recursive" => recursive = true,
+                "-i" | "--ignore-gitignore" => ignore_gitignore = true,
+                "--include-gitignored" => include_gitignored = true,
+                "--icase" | "--ignore-case" => icase = true,
+                "--parallel-collect" => parallel_collect = true,
+                "--one-file-system" => one_file_system = true,
+                "--anchor-patterns" => anchor_patterns = true,
+                "--match-full-path" => match_full_path = true,
+                "--git-stash" => git_stash = true,
+                "--keep-only-changed" => keep_only_changed = true,
+                "--git-untracked-only" => git_untracked_only = true,
+                "--git-deleted-only" => git_deleted_only = true,
+                // Already applied before parsing
+                "--profile" => i += 1,
                 "-v" | "--version" => show_version = true,
+                "--cache-info" => cache_info = true,
+                "--count-tokens" => count_tokens = true,
+                "--stats-only" => stats_only = true,
+                "--sha256" => sha256 = true,
+                "--manifest" => manifest = true,
+                "--symlink-target" => symlink_target = true,
+                "--no-binary-check" => no_binary_check = true,
+                "--no-newline-at-eof" => no_newline_at_eof = true,
+                "--normalise-line-endings" | "--unix-endings" => normalise_line_endings = true,
+                "--add-language-comment" => add_language_comment = true,
+                "--clipboard" | "--output-to-clipboard" => clipboard = true,
+                "--stdin-patterns" => stdin_patterns = true,
+                "--progress" => progress = true,
+                "-V" | "--verbose" => verbose = true,
+                "-q" | "--quiet" => quiet = true,
+                "--emit-null-separator" => emit_null_separator = true,
                 "--url" => {
                     if i + 1 < args.len() {
                         github_url = Some(args[i + 1].clone());
                         i += 1;
                     }
                 }
+                "--github-host" => {
+                    if i + 1 < args.len() {
+                        github_host = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--github-api-version" => {
+                    if i + 1 < args.len() {
+                        github_api_version = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--git-commit" => {
+                    if i + 1 < args.len() {
+                        git_commit = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--branch" => {
+                    if i + 1 < args.len() {
+                        branch = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--path" => {
+                    if i + 1 < args.len() {
+                        path = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--retry" => {
+                    if i + 1 < args.len() {
+                        retry = args[i + 1].parse().ok();
+                        i += 1;
+                    }
+                }
+                "--retry-delay" => {
+                    if i + 1 < args.len() {
+                        retry_delay = args[i + 1].parse().ok();
+                        i += 1;
+                    }
+                }
+                "--redact" => {
+                    if i + 1 < args.len() {
+                        redact_patterns.push(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--redact-replacement" => {
+                    if i + 1 < args.len() {
+                        redact_replacement = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--strip-comments" => {
+                    if i + 1 < args.len() {
+                        strip_comments = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--search" => {
+                    if i + 1 < args.len() {
+                        search = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--context-lines" => {
+                    if i + 1 < args.len() {
+                        context_lines = args[i + 1].parse().ok();
+                        i += 1;
+                    }
+                }
+                "--ignore-gitignore-in" => {
+                    if i + 1 < args.len() {
+                        ignore_gitignore_in.push(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--ignore-pattern" => {
+                    if i + 1 < args.len() {
+                        ignore_patterns.push(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--include-extensions" => {
+                    if i + 1 < args.len() {
+                        include_extensions.extend(Self::parse_extensions(&args[i + 1]));
+                        i += 1;
+                    }
+                }
+                "--exclude-extensions" => {
+                    if i + 1 < args.len() {
+                        exclude_extensions.extend(Self::parse_extensions(&args[i + 1]));
+                        i += 1;
+                    }
+                }
+                "--git-since-ref" => {
+                    if i + 1 < args.len() {
+                        git_since_ref = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--git-author" => {
+                    if i + 1 < args.len() {
+                        git_author = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--git-message-grep" => {
+                    if i + 1 < args.len() {
+                        git_message_grep = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--dir" => {
+                    if i + 1 < args.len() {
+                        dirs.push(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--max-file-count" => {
+                    if i + 1 < args.len() {
+                        max_file_count = args[i + 1].parse().ok();
+                        i += 1;
+                    }
+                }
+                "--file-list" => {
+                    if i + 1 < args.len() {
+                        file_list = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--require-pattern" => {
+                    if i + 1 < args.len() {
+                        require_patterns.push(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--pipe-to" => {
+                    if i + 1 < args.len() {
+                        pipe_to = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--compare" => {
+                    if i + 1 < args.len() {
+                        compare = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--on-success" => {
+                    if i + 1 < args.len() {
+                        on_success = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--on-failure" => {
+                    if i + 1 < args.len() {
+                        on_failure = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--env" => {
+                    if i + 1 < args.len() {
+                        hook_env.push(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--relative-to" => {
+                    if i + 1 < args.len() {
+                        relative_to = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--wrap" => {
+                    if i + 1 < args.len() {
+                        wrap = args[i + 1].parse().ok();
+                        i += 1;
+                    }
+                }
+                "--log-level" => {
+                    if i + 1 < args.len() {
+                        log_level = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--log-format" => {
+                    if i + 1 < args.len() {
+                        log_format = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--encoding-fallback" => {
+                    if i + 1 < args.len() {
+                        encoding_fallback = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--format" => {
+                    if i + 1 < args.len() {
+                        format = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--compress" => {
+                    if i + 1 < args.len() {
+                        compress = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--file-header-template" => {
+                    if i + 1 < args.len() {
+                        file_header_template = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--file-footer-template" => {
+                    if i + 1 < args.len() {
+                        file_footer_template = Some(args[i + 1].clone());
+                        i += 1;
+                    }
+                }
+                "--preview" => {
+                    if i + 1 < args.len() {
+                        preview = args[i + 1].parse().ok();
+                        i += 1;
+                    }
+                }
+                "--benchmark" => {
+                    if i + 1 < args.len() {
+                        benchmark = args[i + 1].parse().ok();
+                        i += 1;
+                    }
+                }
+                // Everything after `--` is a pattern, even if it looks like a flag
+                "--" => {
+                    patterns.extend(args[i + 1..].iter().cloned());
+                    break;
+                }
                 _ => {
                     if !args[i].starts_with('-') {
                         patterns.push(args[i].clone());
@@ -38,35 +532,286 @@ impl CliArgs {
             i += 1;
         }
 
+        if stdin_patterns {
+            patterns.extend(Self::read_stdin_patterns(quiet));
+        }
+
         // If no patterns specified and URL is provided, default to all files
         if patterns.is_empty() && github_url.is_some() {
             patterns.push("*".to_string());
         }
 
-        Self {
+        Ok(Self {
             recursive,
             ignore_gitignore,
+            ignore_gitignore_in,
+            ignore_patterns,
+            include_extensions,
+            exclude_extensions,
+            include_gitignored,
+            icase,
+            anchor_patterns,
+            match_full_path,
+            dirs,
+            git_stash,
+            keep_only_changed,
+            git_untracked_only,
+            git_deleted_only,
+            git_since_ref,
+            git_author,
+            git_message_grep,
+            max_file_count,
+            parallel_collect,
+            one_file_system,
+            require_patterns,
+            file_list,
             patterns,
             github_url,
+            github_host,
+            github_api_version,
+            branch,
+            git_commit,
+            path,
+            retry,
+            retry_delay,
+            progress,
             show_version,
+            cache_info,
+            redact_patterns,
+            redact_replacement,
+            strip_comments,
+            search,
+            context_lines,
+            count_tokens,
+            stats_only,
+            sha256,
+            manifest,
+            symlink_target,
+            no_binary_check,
+            no_newline_at_eof,
+            normalise_line_endings,
+            add_language_comment,
+            clipboard,
+            pipe_to,
+            compare,
+            on_success,
+            on_failure,
+            hook_env,
+            preview,
+            benchmark,
+            relative_to,
+            wrap,
+            encoding_fallback,
+            format,
+            compress,
+            file_header_template,
+            file_footer_template,
+            emit_null_separator,
+            verbose,
+            quiet,
+            log_level,
+            log_format,
+        })
+    }
+
+    fn profile_name(args: &[String]) -> Option<String> {
+        args.iter()
+            .take_while(|arg| *arg != "--")
+            .position(|arg| arg == "--profile")
+            .and_then(|index| args.get(index + 1))
+            .cloned()
+    }
+
+    /// Splits a comma-separated extension list such as `lock,.snap,MD`
+    /// into lowercase extensions without leading dots.
+    fn parse_extensions(list: &str) -> impl Iterator<Item = String> + '_ {
+        list.split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+    }
+
+    /// Reads newline-delimited patterns from stdin, skipping blank lines.
+    fn read_stdin_patterns(quiet: bool) -> Vec<String> {
+        let stdin = io::stdin();
+        if stdin.is_terminal() && !quiet {
+            eprintln!("Enter patterns, one per line (Ctrl-D to finish):");
+        }
+
+        stdin
+            .lock()
+            .lines()
+            .map_while(Result::ok)
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    /// Checks for options that cannot be combined.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quiet && self.verbose {
+            return Err("--quiet and --verbose cannot be used together".to_string());
+        }
+        if self.log_level.is_some() && (self.quiet || self.verbose) {
+            return Err("--log-level cannot be combined with --quiet or --verbose".to_string());
+        }
+        if self.benchmark.is_some_and(|runs| runs < 2) {
+            return Err("--benchmark needs at least 2 runs; the first one is a warmup".to_string());
+        }
+        if let Some(level) = &self.log_level {
+            if !matches!(level.as_str(), "error" | "warn" | "info" | "debug" | "trace") {
+                return Err(format!(
+                    "Unsupported --log-level '{}' (expected error, warn, info, debug or trace)",
+                    level
+                ));
+            }
+        }
+        if let Some(commit) = &self.git_commit {
+            if commit.len() < 7 || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid --git-commit '{}' (expected a hexadecimal commit SHA)", commit));
+            }
+        }
+        for pair in &self.hook_env {
+            if pair.split_once('=').is_none_or(|(key, _)| key.is_empty()) {
+                return Err(format!("Invalid --env '{}' (expected KEY=VALUE)", pair));
+            }
+        }
+        if let Some(format) = &self.log_format {
+            if !matches!(format.as_str(), "text" | "json") {
+                return Err(format!("Unsupported --log-format '{}' (expected text or json)", format));
+            }
         }
+        Ok(())
     }
 
+    /// Whether there is anything to do: a version or cache-info request,
+    /// patterns, a file list, a URL or `--git-deleted-only`, which needs no
+    /// patterns.
     pub fn is_valid(&self) -> bool {
-        self.show_version || !self.patterns.is_empty() || self.github_url.is_some()
+        self.show_version
+            || self.cache_info
+            || !self.patterns.is_empty()
+            || self.file_list.is_some()
+            || self.github_url.is_some()
+            || self.git_deleted_only
     }
 
+    /// Prints the command-line help.
     pub fn print_usage(&self) {
         let program_name = env::args().next().unwrap_or_else(|| String::from("program"));
-        println!("Usage: {} [OPTIONS] [PATTERNS]", program_name);
+        println!("Usage: {} [OPTIONS] [--] [PATTERNS]", program_name);
         println!("\nOptions:");
-        println!("  --url <github_url>  GitHub repository URL");
-        println!("  -r                  Search recursively");
-        println!("  -i                  Ignore .gitignore (include all files)");
+        println!("  --url <github_url>  GitHub repository URL, or file:// URL of a local .tar.gz");
+        println!("  --github-host <host>");
+        println!("                      GitHub Enterprise Server host (defaults to the URL's host)");
+        println!("  --github-api-version <version>");
+        println!("                      REST API version to request (default: 2022-11-28)");
+        println!("  --branch <name>     Branch to download with --url (default: main)");
+        println!("  --git-commit <sha>  Download this commit with --url instead of the branch tip");
+        println!("  --path <subpath>    Repository subdirectory to process with --url");
+        println!("  --retry <N>         Retries for transient download failures (default: 3)");
+        println!("  --retry-delay <ms>  Delay between download retries (default: 1000)");
+        println!("  --progress          Show download progress for --url");
+        println!("  -r, --recursive     Search recursively");
+        println!("  -i, --ignore-gitignore");
+        println!("                      Ignore .gitignore (include all files)");
+        println!("  --ignore-gitignore-in <dir>");
+        println!("                      Don't apply .gitignore below <dir> (repeatable)");
+        println!("  --ignore-pattern <pattern>");
+        println!("                      Also exclude files matching a gitignore-style pattern (repeatable)");
+        println!("  --include-extensions <list>");
+        println!("                      Only include files with these comma-separated extensions, even if named explicitly");
+        println!("  --exclude-extensions <list>");
+        println!("                      Skip files with these comma-separated extensions (e.g. lock,snap)");
+        println!("  --include-gitignored");
+        println!("                      Only include files excluded by .gitignore (everything with -i)");
+        println!("  --icase, --ignore-case");
+        println!("                      Match patterns case-insensitively");
+        println!("  --anchor-patterns   Match patterns from the working directory root only");
+        println!("                      (*.rs skips files in subdirectories)");
+        println!("  --match-full-path   Match patterns against the absolute path, not the file name");
+        println!("                      (patterns containing / always see the path)");
+        println!("  --dir <path>        Also match patterns and relative paths in another directory (repeatable)");
+        println!("  --git-stash         Only include files changed in the most recent git stash");
+        println!("  --keep-only-changed Only include files with uncommitted or untracked changes");
+        println!("  --git-untracked-only");
+        println!("                      Only include files not tracked by git yet");
+        println!("  --git-deleted-only  Output deleted files with their last committed contents");
+        println!("  --git-since-ref <ref>");
+        println!("                      Only include files changed by commits in <ref>..HEAD (e.g. HEAD~5, v1.2.3)");
+        println!("  --git-author <name> Only include files changed by commits from matching authors");
+        println!("  --git-message-grep <pattern>");
+        println!("                      Only include files changed by commits whose message matches");
+        println!("  --max-file-count <N>");
+        println!("                      Stop collecting after N files across all patterns");
+        println!("  --file-list <file>  Aggregate the NUL- or newline-separated paths in <file> (- for stdin)");
+        println!("  --require-pattern <glob>");
+        println!("                      Fail unless some collected file matches <glob> (repeatable)");
+        println!("  --parallel-collect  Check collected entries on all CPU cores");
+        println!("  --one-file-system   Don't descend into directories on other filesystems (mounts)");
+        println!("  --profile <name>    Apply the [profiles.<name>] presets from agg.toml");
         println!("  -v, --version       Show version information");
+        println!("  --cache-info        Show the download cache location, repository count and size");
+        println!("  -V, --verbose       Log every included and skipped file to stderr");
+        println!("  -q, --quiet         Suppress status messages (errors are still shown)");
+        println!("  --log-level <level> Log level: error, warn, info (default), debug or trace; replaces -V/-q");
+        println!("  --log-format <fmt>  Log format on stderr: text (default) or json");
+        println!("  --redact <regex>    Replace matches in file contents (repeatable)");
+        println!("  --redact-replacement <text>");
+        println!("                      Replacement text for --redact (default: <REDACTED>)");
+        println!("  --strip-comments <lang>");
+        println!("                      Remove comments from output (rust, python, js, c)");
+        println!("  --search <regex>    Only include files whose contents match the regex");
+        println!("  --context-lines <N> With --search, only show matching lines and N lines around them");
+        println!("  --count-tokens      Print estimated token counts per file instead of contents");
+        println!("  --stats-only        Print bytes, lines and estimated tokens per file, with totals");
+        println!("  --clipboard         Copy the output to the system clipboard instead of printing it");
+        println!("  --relative-to <base>");
+        println!("                      Show file paths relative to <base> (full path if outside it)");
+        println!("  --wrap <N>          Wrap file lines longer than N characters");
+        println!("  --encoding-fallback <enc>");
+        println!("                      Decode files that aren't UTF-8 as <enc> (e.g. latin1) instead of");
+        println!("                      reporting them as unreadable; default: none");
+        println!("  --pipe-to <cmd>     Write the output to the stdin of a shell command");
+        println!("  --compare <file>    Print a unified diff from a previous run's output instead");
+        println!("  --on-success <cmd>  Run a shell command after a successful run");
+        println!("  --on-failure <cmd>  Run a shell command after a failed run; both hooks get");
+        println!("                      AGG_EXIT_CODE, AGG_FILE_COUNT and AGG_TOTAL_BYTES in their environment");
+        println!("  --env <KEY=VALUE>   Set an environment variable for the hooks (repeatable)");
+        println!("  --format <format>   Output format: text (default), ndjson, html, org or rst");
+        println!("  --compress <algo>   Compress the output: gzip, zstd or none (default)");
+        println!("  --sha256            Append the SHA-256 of each file's raw bytes to its header");
+        println!("  --manifest          Write agg-manifest.json listing the included files and hashes");
+        println!("  --symlink-target    Show symlinked files as '<link> -> <target>' in headers");
+        println!("  --no-binary-check   Include files containing NUL bytes instead of skipping them");
+        println!("  --no-newline-at-eof Strip trailing newlines (LF, CRLF or CR) from each file");
+        println!("  --normalise-line-endings, --unix-endings");
+        println!("                      Convert CRLF and bare CR line endings to LF");
+        println!("  --add-language-comment");
+        println!("                      Start each file with a comment such as '// Language: Rust'");
+        println!("  --file-header-template <str>");
+        println!("  --file-footer-template <str>");
+        println!("                      Text written before/after each file; supports {{path}}, {{basename}},");
+        println!("                      {{ext}}, {{size}}, {{lines}} and escapes such as \\n");
+        println!("  --emit-null-separator");
+        println!("                      Separate files with a NUL byte instead of the footer (stdout only)");
+        println!("  --stdin-patterns    Read additional patterns from stdin, one per line");
+        println!("  --preview <N>       Show the first N characters of the output (highlighted with bat if available)");
+        println!("  --benchmark <N>     Run N times, discarding the output, and print timing statistics");
+        println!("\nExit codes:");
+        println!("  0  Success");
+        println!("  1  No files found");
+        println!("  2  Git error");
+        println!("  3  I/O error");
+        println!("  4  Network error (GitHub download)");
+        println!("  5  Invalid arguments");
         println!("\nExamples:");
         println!("  {} --url 'https://github.com/org/repo/tree/main/path' -r", program_name);
+        println!("  {} --url 'https://github.com/org/repo' --branch develop -r", program_name);
         println!("  {} -r '*.{{rs,toml}}'", program_name);
+        println!("  {} -r '*.env' --redact 'API_KEY=\\S+'", program_name);
+        println!("  {} -r '*.rs' --clipboard", program_name);
+        println!("  git ls-files '*.rs' | {} --stdin-patterns", program_name);
+        println!("  {} --profile llm '*.rs'", program_name);
         println!("  {} --version", program_name);
     }
 }
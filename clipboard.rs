@@ -0,0 +1,53 @@
+use arboard::Clipboard;
+#[cfg(target_os = "linux")]
+use arboard::SetExtLinux;
+use std::error::Error;
+use std::io::{self, Read};
+#[cfg(target_os = "linux")]
+use std::io::Write;
+#[cfg(target_os = "linux")]
+use std::process::{Command, Stdio};
+
+/// Argument that starts the binary as a clipboard owner (see [`serve`])
+/// instead of parsing a command line.
+pub const DAEMON_ARG: &str = "__agg_clipboard_daemon";
+
+/// Puts `text` on the system clipboard.
+///
+/// X11 and Wayland clipboards are only served while the process that set
+/// them is alive, so on Linux the text is handed to a copy of the current
+/// executable started with [`DAEMON_ARG`], which keeps serving it until
+/// something else is copied.
+pub(crate) fn copy(text: String) -> Result<(), Box<dyn Error>> {
+    #[cfg(target_os = "linux")]
+    {
+        // Fails early (no display, ...) where the daemon could only fail silently
+        drop(Clipboard::new()?);
+        let mut daemon = Command::new(std::env::current_exe()?)
+            .arg(DAEMON_ARG)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .current_dir("/")
+            .spawn()?;
+        // Dropping stdin afterwards closes it, so the daemon sees the end of the text
+        daemon.stdin.take().ok_or("clipboard daemon has no stdin")?.write_all(text.as_bytes())?;
+    }
+    #[cfg(not(target_os = "linux"))]
+    Clipboard::new()?.set_text(text)?;
+    Ok(())
+}
+
+/// Reads text from stdin and owns the clipboard with it until something
+/// else is copied. Binaries that use `--clipboard` must call this when
+/// started with [`DAEMON_ARG`] as their first argument.
+pub fn serve() -> Result<(), Box<dyn Error>> {
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+
+    #[cfg(target_os = "linux")]
+    Clipboard::new()?.set().wait().text(text)?;
+    #[cfg(not(target_os = "linux"))]
+    Clipboard::new()?.set_text(text)?;
+    Ok(())
+}
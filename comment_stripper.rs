@@ -0,0 +1,139 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum Language {
+    Rust,
+    Python,
+    Js,
+    C,
+}
+
+impl Language {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "rust" | "rs" => Some(Language::Rust),
+            "python" | "py" => Some(Language::Python),
+            "js" | "javascript" | "ts" | "typescript" => Some(Language::Js),
+            "c" | "cpp" | "c++" => Some(Language::C),
+            _ => None,
+        }
+    }
+
+    fn line_comment(&self) -> &'static str {
+        match self {
+            Language::Python => "#",
+            _ => "//",
+        }
+    }
+
+    fn has_block_comments(&self) -> bool {
+        !matches!(self, Language::Python)
+    }
+
+    fn quotes(&self) -> &'static [char] {
+        match self {
+            Language::Rust => &['"'],
+            Language::Js => &['"', '\'', '`'],
+            _ => &['"', '\''],
+        }
+    }
+}
+
+pub struct CommentStripper {
+    language: Language,
+}
+
+impl CommentStripper {
+    pub fn new(language: Language) -> Self {
+        Self { language }
+    }
+
+    pub fn strip(&self, contents: &str) -> String {
+        let chars: Vec<char> = contents.chars().collect();
+        let line_comment: Vec<char> = self.language.line_comment().chars().collect();
+        let quotes = self.language.quotes();
+
+        let mut output = String::with_capacity(contents.len());
+        let mut line_start = 0;
+        let mut stripped_on_line = false;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if chars[i..].starts_with(&line_comment) {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                stripped_on_line = true;
+                continue;
+            }
+
+            if self.language.has_block_comments() && c == '/' && chars.get(i + 1) == Some(&'*') {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i = (i + 2).min(chars.len());
+                stripped_on_line = true;
+                continue;
+            }
+
+            if self.language == Language::Rust && c == '\'' {
+                // Char literals such as '"' would otherwise open a string
+                let literal_len = match (chars.get(i + 1), chars.get(i + 2)) {
+                    (Some('\\'), _) => chars[i + 2..].iter().position(|&c| c == '\'').map(|p| p + 3),
+                    (Some(_), Some('\'')) => Some(3),
+                    _ => None,
+                };
+                if let Some(len) = literal_len {
+                    output.extend(&chars[i..(i + len).min(chars.len())]);
+                    i += len;
+                    continue;
+                }
+            }
+
+            if quotes.contains(&c) {
+                output.push(c);
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        output.push(chars[i]);
+                        i += 1;
+                    }
+                    output.push(chars[i]);
+                    i += 1;
+                }
+                if i < chars.len() {
+                    output.push(chars[i]);
+                    i += 1;
+                }
+                continue;
+            }
+
+            if c == '\n' {
+                // Drop lines that only contained a comment
+                if stripped_on_line && output[line_start..].trim().is_empty() {
+                    output.truncate(line_start);
+                } else {
+                    if stripped_on_line {
+                        let trimmed_len = output.trim_end_matches([' ', '\t']).len();
+                        output.truncate(trimmed_len.max(line_start));
+                    }
+                    output.push('\n');
+                }
+                line_start = output.len();
+                stripped_on_line = false;
+                i += 1;
+                continue;
+            }
+
+            output.push(c);
+            i += 1;
+        }
+
+        if stripped_on_line && output[line_start..].trim().is_empty() {
+            output.truncate(line_start);
+        }
+
+        output
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+pub const CONFIG_FILE: &str = "agg.toml";
+
+/// Named option presets stored in `agg.toml`:
+///
+/// ```toml
+/// [profiles.llm]
+/// format = "ndjson"
+/// strip_comments = "rust"
+/// recursive = true
+/// redact = ["API_KEY=\\S+"]
+/// ```
+pub struct Config;
+
+impl Config {
+    /// Loads `[profiles.<name>]` from `agg.toml` in the current directory and
+    /// converts it to command-line arguments. Keys are option names without
+    /// the leading dashes (`_` or `-` both work); `true` enables a flag,
+    /// arrays repeat the option and `patterns` supplies positional patterns.
+    pub fn load_profile(name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let path = Path::new(CONFIG_FILE);
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", CONFIG_FILE, e))?;
+        let config: Value = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", CONFIG_FILE, e))?;
+
+        let profile = config
+            .get("profiles")
+            .and_then(|profiles| profiles.get(name))
+            .and_then(Value::as_table)
+            .ok_or_else(|| format!("Profile '{}' not found in {}", name, CONFIG_FILE))?;
+
+        let mut args = Vec::new();
+        for (key, value) in profile {
+            if key == "patterns" {
+                args.extend(Self::values(key, value)?);
+                continue;
+            }
+
+            let flag = format!("--{}", key.replace('_', "-"));
+            match value {
+                Value::Boolean(true) => args.push(flag),
+                Value::Boolean(false) => {}
+                _ => {
+                    for value in Self::values(key, value)? {
+                        args.push(flag.clone());
+                        args.push(value);
+                    }
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn values(key: &str, value: &Value) -> Result<Vec<String>, Box<dyn Error>> {
+        match value {
+            Value::Array(items) => items.iter().map(|item| Self::scalar(key, item)).collect(),
+            _ => Ok(vec![Self::scalar(key, value)?]),
+        }
+    }
+
+    fn scalar(key: &str, value: &Value) -> Result<String, Box<dyn Error>> {
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            Value::Integer(n) => Ok(n.to_string()),
+            Value::Float(n) => Ok(n.to_string()),
+            Value::Boolean(b) => Ok(b.to_string()),
+            _ => Err(format!("Unsupported value for '{}' in {}", key, CONFIG_FILE).into()),
+        }
+    }
+}
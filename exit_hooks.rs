@@ -0,0 +1,54 @@
+use tracing::{info, warn};
+
+use crate::cli::CliArgs;
+use crate::file_processor::ProcessResult;
+use crate::shell::shell_command;
+
+/// The `--on-success` / `--on-failure` commands run once a run finishes.
+pub struct ExitHooks {
+    on_success: Option<String>,
+    on_failure: Option<String>,
+    // Extra variables from --env
+    env: Vec<(String, String)>,
+}
+
+impl ExitHooks {
+    /// Takes the hook commands and `--env` pairs from `args`.
+    pub fn new(args: &CliArgs) -> Self {
+        Self {
+            on_success: args.on_success.clone(),
+            on_failure: args.on_failure.clone(),
+            env: args
+                .hook_env
+                .iter()
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Runs the hook matching `exit_code` through the shell, with the
+    /// `--env` variables set and the outcome exposed as `AGG_EXIT_CODE`,
+    /// `AGG_FILE_COUNT` (also `AGG_FILES_PROCESSED`) and `AGG_TOTAL_BYTES`.
+    /// A failing hook is reported but does not change the exit code.
+    pub fn run(&self, exit_code: i32, result: &ProcessResult) {
+        let hook = if exit_code == 0 { &self.on_success } else { &self.on_failure };
+        let Some(command) = hook else {
+            return;
+        };
+
+        let file_count = result.files_processed.to_string();
+        let status = shell_command(command)
+            .envs(self.env.iter().map(|(key, value)| (key, value)))
+            .env("AGG_EXIT_CODE", exit_code.to_string())
+            .env("AGG_FILE_COUNT", &file_count)
+            .env("AGG_FILES_PROCESSED", &file_count)
+            .env("AGG_TOTAL_BYTES", result.bytes_written.to_string())
+            .status();
+        match status {
+            Ok(status) if status.success() => info!("Hook '{}' finished", command),
+            Ok(status) => warn!("Hook '{}' exited with {}", command, status),
+            Err(e) => warn!("Failed to run hook '{}': {}", command, e),
+        }
+    }
+}
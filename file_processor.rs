@@ -1,81 +1,1087 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
 use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use encoding_rs::Encoding;
+use flate2::write::GzEncoder;
+use futures::stream::{self, BoxStream, StreamExt};
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use sha2::{Digest, Sha256};
+use similar::{DiffTag, TextDiff};
+use tracing::{debug, enabled, error, info, warn, Level};
 use walkdir::WalkDir;
 
 use crate::cli::CliArgs;
-use crate::gitignore_helper::GitignoreHelper;
+use crate::clipboard;
+use crate::comment_stripper::{CommentStripper, Language};
+use crate::file_template::FileTemplate;
+use crate::git_history_handler::GitHistoryHandler;
+use crate::git_status_handler::GitStatusHandler;
+use crate::gitignore_helper::{GitignoreHelper, IgnoreSource};
+use crate::magic_bytes;
+use crate::manifest::{CountingWriter, Manifest};
 use crate::pattern_matcher::PatternMatcher;
+use crate::output_format::{language_comment, Compression, OutputFormat};
+use crate::redactor::Redactor;
+use crate::shell::shell_command;
+use crate::temp_manager::COMPLETE_MARKER;
+use crate::token_estimator::TokenEstimator;
 
+/// Upper bound on files being read at the same time.
+const MAX_CONCURRENT_READS: usize = 64;
+
+/// Files to output, in order, with their raw contents or the read error.
+type FileStream = BoxStream<'static, (PathBuf, io::Result<Vec<u8>>)>;
+
+const DEFAULT_FILE_HEADER: &str = "# File: {path}";
+const DEFAULT_FILE_FOOTER: &str = "\n=====================\n";
+
+
+/// Broad failure categories, each with its own process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// No file matched the given patterns.
+    NoFilesFound,
+    /// A git command failed.
+    Git,
+    /// Reading input or writing output failed.
+    Io,
+    /// Downloading from GitHub failed.
+    Network,
+    /// The command line could not be used as given.
+    InvalidArguments,
+}
+
+impl ErrorKind {
+    /// Exit code reported by the binary for this kind of failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::NoFilesFound => 1,
+            ErrorKind::Git => 2,
+            ErrorKind::Io => 3,
+            ErrorKind::Network => 4,
+            ErrorKind::InvalidArguments => 5,
+        }
+    }
+}
+
+/// Why a candidate file was left out of the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// The file looks like binary data.
+    Binary,
+    /// The file is excluded by a `.gitignore`.
+    GitIgnored,
+    /// The file is excluded by an `--ignore-pattern` rule.
+    CustomIgnored,
+    /// The file's contents don't match `--search`.
+    ContentMismatch,
+}
+
+impl SkipReason {
+    /// Short name used in `--verbose` logs.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkipReason::Binary => "binary",
+            SkipReason::GitIgnored => "gitignore",
+            SkipReason::CustomIgnored => "ignore-pattern",
+            SkipReason::ContentMismatch => "search",
+        }
+    }
+}
+
+/// Outcome of [`FileProcessor::process`].
+#[derive(Debug, Default)]
+pub struct ProcessResult {
+    /// Number of files that were collected and written.
+    pub files_processed: usize,
+    /// Files that matched a pattern but were left out, and why.
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+    /// The category of the failure that stopped processing, if any.
+    pub error_kind: Option<ErrorKind>,
+    /// The I/O error that stopped processing, if any.
+    pub error: Option<io::Error>,
+    /// Bytes of aggregated output written, before any compression.
+    pub bytes_written: u64,
+    /// Wall-clock time from the start of processing to the end of output.
+    pub elapsed: Duration,
+}
+
+impl ProcessResult {
+    /// Exit code for this result: 0 on success, see [`ErrorKind::exit_code`].
+    pub fn exit_code(&self) -> i32 {
+        self.error_kind.map_or(0, |kind| kind.exit_code())
+    }
+}
+
+/// Collects the files selected by a [`CliArgs`] and writes their contents.
 pub struct FileProcessor {
     args: CliArgs,
-    gitignore: Option<ignore::gitignore::Gitignore>,
+    // One per working directory that has .gitignore files or
+    // --ignore-pattern rules, keyed by it
+    gitignores: Vec<(PathBuf, GitignoreHelper)>,
     pattern_matcher: PatternMatcher,
-    working_dir: PathBuf,
+    redactor: Redactor,
+    comment_stripper: Option<CommentStripper>,
+    search: Option<Regex>,
+    format: OutputFormat,
+    compression: Compression,
+    encoding_fallback: Option<&'static Encoding>,
+    // Canonical --relative-to base
+    relative_to: Option<PathBuf>,
+    header_template: FileTemplate,
+    footer_template: FileTemplate,
+    null_separator: bool,
+    working_dirs: Vec<PathBuf>,
+    // Paths read from --file-list
+    file_list: Option<Vec<PathBuf>>,
+    files_to_process: Vec<PathBuf>,
+    collected: bool,
+    manifest: Option<Manifest>,
+    skipped: Vec<(PathBuf, SkipReason)>,
+    bytes_written: u64,
+    // Timing of the current run, reported when it finishes
+    started: Instant,
+    collect_time: Duration,
+    processing_time: Duration,
 }
 
 impl FileProcessor {
-    pub fn new(args: CliArgs, working_dir: PathBuf) -> Self {
-        let gitignore = if !args.ignore_gitignore {
-            GitignoreHelper::build()
-        } else {
-            None
+    /// Creates a processor that resolves patterns relative to `working_dir`
+    /// and any additional `--dir` directories.
+    ///
+    /// Fails if one of the user-supplied regexes or languages is invalid.
+    pub fn new(args: CliArgs, working_dir: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let mut working_dirs = vec![working_dir];
+        working_dirs.extend(args.dirs.iter().map(PathBuf::from));
+
+        // --ignore-pattern rules apply even with -i
+        let gitignores = working_dirs
+            .iter()
+            .filter_map(|dir| {
+                let mut builder = GitignoreHelper::builder(dir);
+                if args.ignore_gitignore {
+                    builder = builder.skip_gitignore_files();
+                }
+                let builder = args
+                    .ignore_patterns
+                    .iter()
+                    .fold(builder, |builder, pattern| builder.pattern(pattern));
+                args.ignore_gitignore_in
+                    .iter()
+                    .fold(builder, |builder, subdir| builder.exempt_dir(subdir))
+                    .build()
+                    .map(|helper| (dir.clone(), helper))
+            })
+            .collect();
+
+        let replacement = args.redact_replacement.as_deref().unwrap_or("<REDACTED>");
+        let redactor = Redactor::new(&args.redact_patterns, replacement)
+            .map_err(|e| format!("Invalid --redact pattern: {}", e))?;
+
+        let comment_stripper = match &args.strip_comments {
+            Some(lang) => {
+                let language = Language::from_name(lang).ok_or_else(|| {
+                    format!("Unsupported --strip-comments language '{}' (expected rust, python, js or c)", lang)
+                })?;
+                Some(CommentStripper::new(language))
+            }
+            None => None,
+        };
+
+        let search = match &args.search {
+            // Multi-line mode so ^ and $ anchor to lines, as with grep
+            Some(pattern) => Some(
+                RegexBuilder::new(pattern)
+                    .multi_line(true)
+                    .build()
+                    .map_err(|e| format!("Invalid --search pattern: {}", e))?,
+            ),
+            None => None,
+        };
+
+        let format = match &args.format {
+            Some(name) => OutputFormat::from_name(name)
+                .ok_or_else(|| format!("Unsupported --format '{}' (expected text, ndjson, html, org or rst)", name))?,
+            None => OutputFormat::default(),
+        };
+        // Document formats lay out each file themselves and need their start
+        // and end around the whole output
+        if matches!(format, OutputFormat::Html | OutputFormat::Org | OutputFormat::Rst) {
+            let text_only = [
+                (args.sha256, "--sha256"),
+                (args.symlink_target, "--symlink-target"),
+                (args.file_header_template.is_some(), "--file-header-template"),
+                (args.file_footer_template.is_some(), "--file-footer-template"),
+                (args.wrap.is_some_and(|width| width > 0), "--wrap"),
+                (args.emit_null_separator, "--emit-null-separator"),
+                (args.preview.is_some(), "--preview"),
+            ];
+            if let Some((_, flag)) = text_only.iter().find(|(set, _)| *set) {
+                let name = args.format.as_deref().unwrap_or_default();
+                return Err(format!("{} cannot be used with --format {}", flag, name).into());
+            }
+        }
+
+        let compression = match &args.compress {
+            Some(name) => Compression::from_name(name)
+                .ok_or_else(|| format!("Unsupported --compress '{}' (expected gzip, zstd or none)", name))?,
+            None => Compression::default(),
+        };
+        if compression != Compression::None && (args.clipboard || args.compare.is_some()) {
+            return Err("--compress cannot be used with --clipboard or --compare".into());
+        }
+
+        // "none" keeps the default of reporting files that aren't UTF-8
+        let encoding_fallback = match args.encoding_fallback.as_deref() {
+            None | Some("none") => None,
+            Some(label) => Some(
+                Encoding::for_label(label.as_bytes())
+                    .ok_or_else(|| format!("Unknown --encoding-fallback '{}'", label))?,
+            ),
+        };
+
+        let relative_to = match &args.relative_to {
+            Some(base) => Some(
+                fs::canonicalize(base).map_err(|e| format!("Invalid --relative-to '{}': {}", base, e))?,
+            ),
+            None => None,
+        };
+
+        let header_template =
+            FileTemplate::new(args.file_header_template.as_deref().unwrap_or(DEFAULT_FILE_HEADER));
+        let footer_template =
+            FileTemplate::new(args.file_footer_template.as_deref().unwrap_or(DEFAULT_FILE_FOOTER));
+
+        // NUL bytes are meant for programs reading stdout, not for text
+        // that ends up in front of a person
+        let null_separator = args.emit_null_separator && !args.clipboard;
+        if args.emit_null_separator && args.clipboard {
+            warn!("--emit-null-separator only applies to stdout output; ignoring it with --clipboard");
+        }
+
+        let pattern_matcher = PatternMatcher::new(args.icase, args.anchor_patterns);
+        // Existing paths are used as they are, not as globs
+        let globs = args
+            .patterns
+            .iter()
+            .filter(|pattern| Self::existing_paths(pattern, &working_dirs).is_empty());
+        for pattern in globs.chain(&args.require_patterns) {
+            pattern_matcher
+                .glob_to_regex(pattern)
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        }
+
+        let file_list = match &args.file_list {
+            Some(source) => Some(
+                Self::read_file_list(source)
+                    .map_err(|e| format!("Failed to read --file-list '{}': {}", source, e))?,
+            ),
+            None => None,
         };
 
-        Self {
+        Ok(Self {
             args,
-            gitignore,
-            pattern_matcher: PatternMatcher::new(),
-            working_dir,
+            gitignores,
+            pattern_matcher,
+            redactor,
+            comment_stripper,
+            search,
+            format,
+            compression,
+            encoding_fallback,
+            relative_to,
+            header_template,
+            footer_template,
+            null_separator,
+            working_dirs,
+            file_list,
+            files_to_process: Vec::new(),
+            collected: false,
+            manifest: None,
+            skipped: Vec::new(),
+            bytes_written: 0,
+            started: Instant::now(),
+            collect_time: Duration::ZERO,
+            processing_time: Duration::ZERO,
+        })
+    }
+
+    /// Number of files the patterns match after the git filters (or the
+    /// number of deleted files with `--git-deleted-only`), collecting them
+    /// if that hasn't happened yet. A later [`process`](Self::process)
+    /// reuses the result.
+    ///
+    /// Fails if one of the git queries does.
+    pub fn count_files(&mut self) -> Result<usize, Box<dyn Error>> {
+        self.ensure_collected()?;
+        Ok(self.files_to_process.len())
+    }
+
+    /// Collects the matching files and writes them to stdout (or the
+    /// clipboard, or a token report, depending on the options).
+    ///
+    /// On Linux `--clipboard` re-runs the current executable to keep
+    /// serving the clipboard; see [`clipboard::serve`].
+    pub async fn process(&mut self) -> ProcessResult {
+        let files = match self.start(self.output_target()) {
+            Ok(files) => files,
+            Err(result) => return result,
+        };
+        let written = if self.args.stats_only {
+            self.print_stats(files).await
+        } else if self.args.count_tokens {
+            self.print_token_counts(files).await
+        } else if let Some(limit) = self.args.preview {
+            self.print_preview(files, limit).await
+        } else if let Some(previous) = self.args.compare.clone() {
+            self.compare_with(files, &previous).await
+        } else if let Some(command) = self.args.pipe_to.clone() {
+            self.pipe_to(files, &command).await
+        } else if self.args.clipboard {
+            let mut buffer = Vec::new();
+            match self.write_files(files, &mut buffer).await {
+                Ok(0) => Ok(0),
+                Ok(count) => self.copy_to_clipboard(buffer).map(|_| count),
+                Err(e) => Err(e),
+            }
+        } else {
+            self.write_compressed(files, &mut io::stdout().lock()).await
+        };
+        self.finish(written)
+    }
+
+    /// Collects the matching files and writes the aggregated output to
+    /// `writer` instead, ignoring the options that pick another target or
+    /// a report (`--pipe-to`, `--clipboard`, `--stats-only`, ...).
+    pub async fn process_into<W: Write>(&mut self, mut writer: W) -> ProcessResult {
+        let files = match self.start("writer".to_string()) {
+            Ok(files) => files,
+            Err(result) => return result,
+        };
+        let written = self.write_compressed(files, &mut writer).await;
+        self.finish(written)
+    }
+
+    /// Runs collection and output `runs` times, discarding the output, and
+    /// prints a table of wall-clock times. The first run only warms caches
+    /// up and is left out of the statistics, so at least two runs are
+    /// needed. Stops at the first failure.
+    pub async fn benchmark(&mut self, runs: usize) -> ProcessResult {
+        if runs < 2 {
+            error!("Benchmarking needs at least 2 runs; the first one is a warmup");
+            return ProcessResult { error_kind: Some(ErrorKind::InvalidArguments), ..Default::default() };
         }
+        let mut times = Vec::with_capacity(runs);
+        let mut result = ProcessResult::default();
+        for run in 0..runs {
+            self.collected = false;
+            self.bytes_written = 0;
+            self.processing_time = Duration::ZERO;
+            result = self.process_into(io::sink()).await;
+            if result.error_kind.is_some() {
+                return result;
+            }
+            if run > 0 {
+                times.push(result.elapsed.as_secs_f64());
+            }
+        }
+
+        times.sort_by(f64::total_cmp);
+        let mean = times.iter().sum::<f64>() / times.len() as f64;
+        let middle = times.len() / 2;
+        let median = if times.len() % 2 == 0 {
+            (times[middle - 1] + times[middle]) / 2.0
+        } else {
+            times[middle]
+        };
+        println!("{:<6} {:>9} {:>9} {:>9} {:>9}", "runs", "min", "max", "mean", "median");
+        println!(
+            "{:<6} {:>8.3}s {:>8.3}s {:>8.3}s {:>8.3}s",
+            times.len(),
+            times[0],
+            times[times.len() - 1],
+            mean,
+            median
+        );
+        result
     }
 
-    pub fn process(&self) {
+    /// Collects and filters the files and opens them for reading, or
+    /// returns the result of a run that failed before any output.
+    fn start(&mut self, output_target: String) -> Result<FileStream, ProcessResult> {
+        self.started = Instant::now();
+        let fail = |kind, e: Box<dyn Error>| {
+            error!("{}", e);
+            ProcessResult { error_kind: Some(kind), ..Default::default() }
+        };
+
+        self.ensure_collected().map_err(|e| fail(ErrorKind::Git, e))?;
+        self.check_required_patterns().map_err(|e| fail(ErrorKind::NoFilesFound, e))?;
+
+        if self.args.manifest {
+            self.manifest = Some(Manifest::new(output_target));
+        }
+
+        let files = if self.args.git_deleted_only {
+            self.read_deleted_files()
+        } else {
+            self.read_files()
+        };
+        self.collect_time = self.started.elapsed();
+        Ok(files)
+    }
+
+    /// Turns the outcome of writing the output into the run's result,
+    /// writing the manifest if everything went well.
+    fn finish(&mut self, written: io::Result<usize>) -> ProcessResult {
+        let elapsed = self.started.elapsed();
+        let mut result = ProcessResult {
+            skipped: std::mem::take(&mut self.skipped),
+            bytes_written: self.bytes_written,
+            elapsed,
+            ..Default::default()
+        };
+        match written {
+            Ok(0) => {
+                info!("No files found matching the patterns.");
+                result.error_kind = Some(ErrorKind::NoFilesFound);
+            }
+            Ok(count) => {
+                result.files_processed = count;
+                if let Some(Err(e)) = self.manifest.take().map(|m| m.write(&self.working_dirs)) {
+                    error!("Failed to write the manifest: {}", e);
+                    result.error_kind = Some(ErrorKind::Io);
+                    result.error = Some(e);
+                }
+            }
+            Err(e) => {
+                result.error_kind = Some(ErrorKind::Io);
+                result.error = Some(e);
+            }
+        }
+
+        info!("Processing complete! ({:.2}s)", elapsed.as_secs_f64());
+        if enabled!(Level::DEBUG) {
+            // Reading overlaps with writing, so they are reported together
+            let output_time = elapsed.saturating_sub(self.collect_time + self.processing_time);
+            debug!(
+                "Collection {:.2}s, processing {:.2}s, reading and writing {:.2}s",
+                self.collect_time.as_secs_f64(),
+                self.processing_time.as_secs_f64(),
+                output_time.as_secs_f64()
+            );
+        }
+        result
+    }
+
+    /// Where the aggregated output goes, as recorded in the manifest.
+    fn output_target(&self) -> String {
+        match (&self.args.compare, &self.args.pipe_to) {
+            (Some(previous), _) => format!("diff: {}", previous),
+            (None, Some(command)) => format!("pipe: {}", command),
+            (None, None) if self.args.clipboard => "clipboard".to_string(),
+            (None, None) => "stdout".to_string(),
+        }
+    }
+
+    /// Reads the collected files concurrently, yielding them in order.
+    fn read_files(&self) -> FileStream {
+        stream::iter(self.files_to_process.clone())
+            .map(|path| async move {
+                let contents = tokio::fs::read(&path).await;
+                (path, contents)
+            })
+            .buffered(MAX_CONCURRENT_READS)
+            .boxed()
+    }
+
+    /// The collected deleted files, read from `HEAD` with `git show` rather
+    /// than from disk. A file git can't show is still output, with an error
+    /// note in place of its contents.
+    fn read_deleted_files(&self) -> FileStream {
+        let files: Vec<_> = self
+            .files_to_process
+            .iter()
+            .map(|path| {
+                let dir = self.owning_dir(path);
+                let relative = path.strip_prefix(dir).unwrap_or(path);
+                let contents = GitStatusHandler::new(dir).head_contents(relative).map_err(|e| {
+                    warn!("Could not read {} from HEAD: {}", relative.display(), e);
+                    io::Error::other(e.to_string())
+                });
+                (path.clone(), contents)
+            })
+            .collect();
+        stream::iter(files).boxed()
+    }
+
+    /// The deleted files in every working directory that pass the extension
+    /// filters and the positional patterns.
+    fn collect_deleted_files(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut files = Vec::new();
+        for dir in &self.working_dirs {
+            for path in GitStatusHandler::new(dir).changes()?.deleted_files {
+                let path = dir.join(path);
+                if self.extension_allowed(&path) && self.matches_deleted(&path, dir) {
+                    files.push(path);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Whether a deleted file is selected by the positional patterns: a glob
+    /// or the path of a directory it used to live in. No patterns select
+    /// every deleted file.
+    fn matches_deleted(&self, path: &Path, dir: &Path) -> bool {
+        self.args.patterns.is_empty()
+            || self.args.patterns.iter().any(|pattern| {
+                path.strip_prefix(dir).is_ok_and(|relative| relative.starts_with(pattern))
+                    || self
+                        .pattern_matcher
+                        .glob_to_regex(pattern)
+                        .is_ok_and(|regex| self.matches_glob(pattern, &regex, path, dir))
+            })
+    }
+
+    async fn write_files(
+        &mut self,
+        mut files: FileStream,
+        output: &mut impl Write,
+    ) -> io::Result<usize> {
+        let mut output = CountingWriter::new(output);
+        let output = &mut output;
+        let mut count = 0;
+        let mut html_paths = Vec::new();
+        while let Some((path, contents)) = files.next().await {
+            let sha256 = self.sha256(&contents);
+            // The manifest describes the file on disk, before any transformation
+            let raw = match (&self.manifest, &contents) {
+                (Some(_), Ok(bytes)) => Some((bytes.len(), format!("{:x}", Sha256::digest(bytes)))),
+                _ => None,
+            };
+            let Some(contents) = self.prepare_contents(&path, contents) else {
+                continue;
+            };
+            if let (Some((size, digest)), Ok(text)) = (raw, &contents) {
+                let display_path = self.display_path(&path);
+                if let Some(manifest) = &mut self.manifest {
+                    manifest.add(display_path, size, digest, text);
+                }
+            }
+            if self.format == OutputFormat::Html {
+                if count == 0 {
+                    OutputFormat::write_html_start(output)?;
+                }
+                html_paths.push(self.display_path(&path));
+            }
+            if count == 0 {
+                match self.format {
+                    OutputFormat::Org => OutputFormat::write_org_start(output)?,
+                    OutputFormat::Rst => OutputFormat::write_rst_start(output)?,
+                    _ => {}
+                }
+            }
+            self.process_single_file(&path, &contents, sha256.as_deref(), output)?;
+            count += 1;
+        }
+        if count > 0 && self.format == OutputFormat::Html {
+            OutputFormat::write_html_end(&html_paths, output)?;
+        }
+        output.flush()?;
+        self.bytes_written += output.bytes;
+        if let Some(manifest) = &mut self.manifest {
+            manifest.add_output_bytes(output.bytes);
+        }
+        Ok(count)
+    }
+
+    /// Runs `write_files` through the `--compress` encoder, finishing the
+    /// stream so its trailer is written.
+    async fn write_compressed(
+        &mut self,
+        files: FileStream,
+        output: &mut impl Write,
+    ) -> io::Result<usize> {
+        match self.compression {
+            Compression::None => self.write_files(files, output).await,
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(output, flate2::Compression::default());
+                let count = self.write_files(files, &mut encoder).await?;
+                encoder.finish()?;
+                Ok(count)
+            }
+            Compression::Zstd => {
+                let mut encoder = zstd::Encoder::new(output, 0)?;
+                let count = self.write_files(files, &mut encoder).await?;
+                encoder.finish()?;
+                Ok(count)
+            }
+        }
+    }
+
+    /// Streams the output into the stdin of a shell command and reports
+    /// its exit status once it finishes.
+    async fn pipe_to(
+        &mut self,
+        files: FileStream,
+        command: &str,
+    ) -> io::Result<usize> {
+        let mut child = shell_command(command)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::other(format!("failed to run '{}': {}", command, e)))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let written = self.write_compressed(files, &mut stdin).await;
+        // Closing stdin lets the command see end of input
+        drop(stdin);
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("'{}' exited with {}", command, status)));
+        }
+        match written {
+            // A command that stops reading early (like `head`) and succeeds got what it wanted
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(self.files_to_process.len()),
+            written => written,
+        }
+    }
+
+    /// Renders the output in memory and prints a unified diff against the
+    /// output of an earlier run saved in `previous`.
+    async fn compare_with(&mut self, files: FileStream, previous: &str) -> io::Result<usize> {
+        let old = fs::read_to_string(previous)
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to read {}: {}", previous, e)))?;
+        let mut buffer = Vec::new();
+        let count = self.write_files(files, &mut buffer).await?;
+        let new = String::from_utf8_lossy(&buffer);
+
+        let diff = TextDiff::from_lines(old.as_str(), new.as_ref());
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "{}", diff.unified_diff().header(previous, "current"))?;
+        stdout.flush()?;
+
+        if diff.ops().iter().all(|op| op.tag() == DiffTag::Equal) {
+            info!("Output matches {}", previous);
+        } else {
+            info!("Output differs from {}", previous);
+        }
+        Ok(count)
+    }
+
+    fn copy_to_clipboard(&self, buffer: Vec<u8>) -> io::Result<()> {
+        let contents = String::from_utf8_lossy(&buffer).into_owned();
+        let bytes = contents.len();
+        let tokens = TokenEstimator::estimate(&contents);
+
+        clipboard::copy(contents).map_err(|e| io::Error::other(format!("clipboard unavailable: {}", e)))?;
+
+        info!("Copied {} bytes (~{} tokens) to the clipboard", bytes, tokens);
+        Ok(())
+    }
+
+    async fn print_preview(
+        &mut self,
+        mut files: FileStream,
+        limit: usize,
+    ) -> io::Result<usize> {
+        let mut preview = String::new();
+        let mut preview_chars = 0;
+        let mut shown_files = 0;
+        let mut total_files = 0;
+        let mut total_bytes = 0;
+        let mut language = None;
+
+        while let Some((path, contents)) = files.next().await {
+            let sha256 = self.sha256(&contents);
+            let Some(contents) = self.prepare_contents(&path, contents) else {
+                continue;
+            };
+            let mut buffer = Vec::new();
+            self.process_single_file(&path, &contents, sha256.as_deref(), &mut buffer)?;
+            let rendered = String::from_utf8_lossy(&buffer);
+            total_files += 1;
+            total_bytes += rendered.len();
+
+            if language.is_none() {
+                language = path.extension().and_then(|ext| ext.to_str()).map(String::from);
+            }
+
+            if preview_chars < limit {
+                let taken: String = rendered.chars().take(limit - preview_chars).collect();
+                preview_chars += taken.chars().count();
+                preview.push_str(&taken);
+                shown_files += 1;
+            }
+        }
+
+        if total_files == 0 {
+            return Ok(0);
+        }
+
+        Self::print_highlighted(&preview, language.as_deref())?;
+
+        println!(
+            "\n[preview] {} more files and {} more bytes not shown ({} files, {} bytes in total)",
+            total_files - shown_files,
+            total_bytes - preview.len(),
+            total_files,
+            total_bytes
+        );
+        Ok(total_files)
+    }
+
+    /// Pipes `text` through `bat` for syntax highlighting when printing to a
+    /// terminal, falling back to plain output if it isn't installed.
+    fn print_highlighted(text: &str, language: Option<&str>) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+
+        if stdout.is_terminal() {
+            for program in ["bat", "batcat"] {
+                let mut command = Command::new(program);
+                command.args(["--color=always", "--paging=never", "--style=plain"]);
+                if let Some(language) = language {
+                    command.args(["--language", language]);
+                }
+
+                let child = command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn();
+                let Ok(mut child) = child else {
+                    continue;
+                };
+
+                // Write from a separate thread so a full stdout pipe can't deadlock us
+                let mut stdin = child.stdin.take().expect("stdin is piped");
+                let input = text.to_string();
+                let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+                let output = child.wait_with_output()?;
+                let _ = writer.join();
+                if output.status.success() {
+                    return stdout.write_all(&output.stdout);
+                }
+            }
+        }
+
+        stdout.write_all(text.as_bytes())
+    }
+
+    fn ensure_collected(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.collected {
+            return Ok(());
+        }
+        if self.args.git_deleted_only {
+            self.files_to_process = self.collect_deleted_files()?;
+        } else {
+            let mut skipped = Vec::new();
+            self.files_to_process = self.collect_files(&mut skipped);
+            self.skipped = skipped;
+            self.apply_git_filters()?;
+        }
+        self.collected = true;
+        Ok(())
+    }
+
+    fn collect_files(&self, skipped: &mut Vec<(PathBuf, SkipReason)>) -> Vec<PathBuf> {
+        let mut files = CollectedFiles::new(self.args.max_file_count);
+
+        if let Some(list) = &self.file_list {
+            self.collect_from_file_list(list, &mut files, skipped);
+        }
         for pattern in &self.args.patterns {
-            let path = Path::new(pattern);
-            if path.exists() {
-                if path.is_dir() {
-                    self.process_directory(path);
-                } else {
-                    self.process_single_file(path);
+            if files.truncated {
+                break;
+            }
+            let before = files.len();
+            let paths = Self::existing_paths(pattern, &self.working_dirs);
+            if !paths.is_empty() {
+                for path in paths {
+                    if path.is_dir() {
+                        self.collect_from_directory(&path, &mut files, skipped);
+                    } else if self.extension_allowed(&path) {
+                        // Naming a file bypasses .gitignore, but not the extension filters
+                        files.push(path);
+                    }
                 }
             } else {
-                // Treat as a glob pattern
-                self.process_glob_pattern(pattern);
+                // Treat as a glob pattern, matched in every working directory
+                for dir in &self.working_dirs {
+                    self.collect_from_glob_pattern(pattern, dir, &mut files, skipped);
+                }
             }
+            // Makes a typo in one of several patterns easy to spot
+            if files.len() == before && !files.truncated {
+                info!("Pattern '{}' matched 0 files", pattern);
+            }
+        }
+
+        if let Some(max) = self.args.max_file_count.filter(|_| files.truncated) {
+            warn!(
+                "stopped collecting after {} files (--max-file-count); remaining matches were ignored",
+                max
+            );
+        }
+
+        skipped.sort();
+        skipped.dedup();
+        files.paths
+    }
+
+    /// The paths `pattern` names: relative to the current directory for the
+    /// main working directory, and below each `--dir` directory. Empty if
+    /// it names nothing, making it a glob.
+    fn existing_paths(pattern: &str, working_dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let path = Path::new(pattern);
+        let mut candidates = vec![path.to_path_buf()];
+        if path.is_relative() {
+            candidates.extend(working_dirs.iter().skip(1).map(|dir| dir.join(path)));
         }
+        candidates.retain(|candidate| candidate.exists());
+        candidates
     }
 
-    fn process_glob_pattern(&self, pattern: &str) {
-        let regex = self.pattern_matcher.glob_to_regex(pattern);
-        let walker = self.create_walker();
+    /// Takes the `--file-list` paths instead of walking, applying the same
+    /// filters a walked file would get.
+    fn collect_from_file_list(
+        &self,
+        list: &[PathBuf],
+        files: &mut CollectedFiles,
+        skipped: &mut Vec<(PathBuf, SkipReason)>,
+    ) {
+        for path in list {
+            if files.truncated {
+                break;
+            }
+            if !path.is_file() {
+                warn!("--file-list entry '{}' is not a file", path.display());
+                continue;
+            }
+            if path.components().any(|c| c.as_os_str() == ".git") {
+                continue;
+            }
+            if let Some(reason) = self.hidden_reason(path).filter(|&reason| self.excludes(reason)) {
+                self.log_skip(path, reason);
+                skipped.push((path.clone(), reason));
+                continue;
+            }
+            if self.is_eligible(path) {
+                files.push(path.clone());
+            }
+        }
+    }
+
+    /// Reads the paths in a `--file-list` file (`-` for stdin), separated
+    /// by NULs if there are any and by newlines otherwise.
+    fn read_file_list(source: &str) -> io::Result<Vec<PathBuf>> {
+        let bytes = if source == "-" {
+            let mut bytes = Vec::new();
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            bytes
+        } else {
+            fs::read(source)?
+        };
+        let text = String::from_utf8_lossy(&bytes);
+        let separator = if text.contains('\0') { '\0' } else { '\n' };
+        Ok(text
+            .split(separator)
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    fn collect_from_glob_pattern(
+        &self,
+        pattern: &str,
+        dir: &Path,
+        files: &mut CollectedFiles,
+        skipped: &mut Vec<(PathBuf, SkipReason)>,
+    ) {
+        // Checked in new(); only a pattern that stopped naming a path gets here
+        let regex = match self.pattern_matcher.glob_to_regex(pattern) {
+            Ok(regex) => regex,
+            Err(e) => {
+                warn!("Skipping invalid pattern '{}': {}", pattern, e);
+                return;
+            }
+        };
+        let walker = self.create_walker(dir);
         
-        for entry in walker.into_iter().filter_entry(|e| self.should_process_entry(e.path())) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_file() && regex.is_match(path.to_str().unwrap_or("")) {
-                    self.process_single_file(path);
+        let filter = |e: &walkdir::DirEntry| {
+            let keep = self.should_process_entry(e.path());
+            // Only report ignored files the pattern would otherwise have picked up
+            if !keep && e.file_type().is_file() && self.matches_glob(pattern, &regex, e.path(), dir) {
+                if let Some(reason) = self.ignore_reason(e.path()) {
+                    self.log_skip(e.path(), reason);
+                    skipped.push((e.path().to_path_buf(), reason));
+                }
+            }
+            keep
+        };
+        let entries = walker.into_iter().filter_entry(filter).flatten();
+        let is_match =
+            |path: &Path| path.is_file() && self.matches_glob(pattern, &regex, path, dir) && self.is_eligible(path);
+        self.push_matches(entries, is_match, files);
+    }
+
+    /// Appends the walked entries accepted by `is_match` until the file
+    /// limit is reached. With `--parallel-collect` the walk finishes first
+    /// and the checks run on the rayon pool; either way matches keep the
+    /// walk order.
+    fn push_matches(
+        &self,
+        entries: impl Iterator<Item = walkdir::DirEntry>,
+        is_match: impl Fn(&Path) -> bool + Sync,
+        files: &mut CollectedFiles,
+    ) {
+        if self.args.parallel_collect {
+            let paths: Vec<PathBuf> = entries.map(walkdir::DirEntry::into_path).collect();
+            let matched: Vec<PathBuf> = paths.into_par_iter().filter(|path| is_match(path)).collect();
+            for path in matched {
+                files.push(path);
+                if files.truncated {
+                    break;
+                }
+            }
+            return;
+        }
+
+        for entry in entries {
+            let path = entry.path();
+            if is_match(path) {
+                files.push(path.to_path_buf());
+                if files.truncated {
+                    break;
                 }
             }
         }
     }
 
-    fn process_directory(&self, dir: &Path) {
-        let walker = WalkDir::new(dir).into_iter();
-        for entry in walker.filter_entry(|e| self.should_process_entry(e.path())) {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_file() {
-                    self.process_single_file(path);
+    /// Narrows the collected files to those selected by `--git-stash`,
+    /// `--keep-only-changed`, `--git-untracked-only` and the commit filters (`--git-since-ref`,
+    /// `--git-author`, `--git-message-grep`).
+    fn apply_git_filters(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.args.git_stash {
+            let stashed = self.query_git(|dir| GitStatusHandler::new(dir).stashed_files())?;
+            self.retain_files(&stashed);
+        }
+        if self.args.keep_only_changed {
+            let changed = self.query_git(|dir| Ok(GitStatusHandler::new(dir).changes()?.changed_files))?;
+            self.retain_files(&changed);
+        }
+        if self.args.git_untracked_only {
+            let untracked = self.query_git(|dir| Ok(GitStatusHandler::new(dir).changes()?.untracked_files))?;
+            self.retain_files(&untracked);
+        }
+        let args = &self.args;
+        let filters_commits = [&args.git_since_ref, &args.git_author, &args.git_message_grep]
+            .iter()
+            .any(|filter| filter.is_some());
+        if filters_commits {
+            let committed = self.query_git(|dir| {
+                let history = GitHistoryHandler::new(dir, &self.args);
+                match &self.args.git_since_ref {
+                    Some(since_ref) => history.get_changed_since_ref(since_ref),
+                    None => history.get_committed_files(),
                 }
+            })?;
+            self.retain_files(&committed);
+        }
+        Ok(())
+    }
+
+    /// Runs a git query in every working directory and merges the results.
+    fn query_git(
+        &self,
+        query: impl Fn(&Path) -> Result<HashSet<PathBuf>, Box<dyn Error>>,
+    ) -> Result<HashSet<PathBuf>, Box<dyn Error>> {
+        let mut paths = HashSet::new();
+        for dir in &self.working_dirs {
+            paths.extend(query(dir)?);
+        }
+        Ok(paths)
+    }
+
+    /// Keeps the collected files whose canonical path is in `keep`.
+    fn retain_files(&mut self, keep: &HashSet<PathBuf>) {
+        self.files_to_process.retain(|path| {
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            keep.contains(&path)
+        });
+    }
+
+    /// Fails on the first `--require-pattern` glob no collected file matches.
+    fn check_required_patterns(&self) -> Result<(), Box<dyn Error>> {
+        for pattern in &self.args.require_patterns {
+            let regex = self.pattern_matcher.glob_to_regex(pattern)?;
+            // Files named on the command line needn't start with their working directory
+            let found = self
+                .files_to_process
+                .iter()
+                .any(|path| self.matches_glob(pattern, &regex, path, self.owning_dir(path)));
+            if !found {
+                return Err(format!("No collected file matches required pattern '{}'", pattern).into());
             }
         }
+        Ok(())
+    }
+
+    /// With `--match-full-path` patterns are matched against the absolute
+    /// path. Otherwise anchored patterns see the path relative to the
+    /// working directory `dir`, patterns containing `/` the walked path, and
+    /// all others just the file name, so `a*.rs` doesn't match `abc/x.rs`.
+    fn matches_glob(&self, pattern: &str, regex: &Regex, path: &Path, dir: &Path) -> bool {
+        let target: Cow<Path> = if self.args.match_full_path {
+            std::path::absolute(path).map_or(Cow::Borrowed(path), Cow::Owned)
+        } else if self.pattern_matcher.is_anchored() {
+            Cow::Borrowed(path.strip_prefix(dir).unwrap_or(path))
+        } else if pattern.contains('/') {
+            Cow::Borrowed(path)
+        } else {
+            Cow::Borrowed(path.file_name().map_or(path, Path::new))
+        };
+        regex.is_match(target.to_str().unwrap_or(""))
+    }
+
+    fn collect_from_directory(
+        &self,
+        dir: &Path,
+        files: &mut CollectedFiles,
+        skipped: &mut Vec<(PathBuf, SkipReason)>,
+    ) {
+        let walker = WalkDir::new(dir).same_file_system(self.args.one_file_system).into_iter();
+        let filter = |e: &walkdir::DirEntry| {
+            let keep = self.should_process_entry(e.path());
+            if !keep && e.file_type().is_file() {
+                if let Some(reason) = self.ignore_reason(e.path()) {
+                    self.log_skip(e.path(), reason);
+                    skipped.push((e.path().to_path_buf(), reason));
+                }
+            }
+            keep
+        };
+        let entries = walker.filter_entry(filter).flatten();
+        self.push_matches(entries, |path| path.is_file() && self.is_eligible(path), files);
     }
 
-    fn create_walker(&self) -> WalkDir {
+    fn create_walker(&self, dir: &Path) -> WalkDir {
+        // --one-file-system: don't descend into mount points (like find -xdev)
+        let walker = WalkDir::new(dir).same_file_system(self.args.one_file_system);
         if self.args.recursive {
-            WalkDir::new(&self.working_dir)
+            walker
         } else {
-            WalkDir::new(&self.working_dir).max_depth(1)
+            walker.max_depth(1)
         }
     }
 
@@ -85,22 +1091,504 @@ impl FileProcessor {
             return false;
         }
 
-        // Then check gitignore if enabled
-        if let Some(gi) = &self.gitignore {
-            !gi.matched(path, path.is_dir()).is_ignore()
-        } else {
-            true
+        // Skip the marker left in downloaded repositories
+        if path.file_name().is_some_and(|name| name == COMPLETE_MARKER) {
+            return false;
         }
+
+        // Then check the ignore rules; --include-gitignored has to see
+        // inside directories .gitignore excludes
+        self.ignore_reason(path).is_none_or(|reason| !self.excludes(reason))
+    }
+
+    /// Whether files ignored for `reason` are left out: `--include-gitignored`
+    /// keeps .gitignore'd files, but `--ignore-pattern` always excludes.
+    fn excludes(&self, reason: SkipReason) -> bool {
+        reason != SkipReason::GitIgnored || !self.only_gitignored()
+    }
+
+    /// `--include-gitignored` without `-i`: keep ignored files only.
+    fn only_gitignored(&self) -> bool {
+        self.args.include_gitignored && !self.args.ignore_gitignore
+    }
+
+    /// File-level filters applied to walked files before they are read.
+    fn is_eligible(&self, path: &Path) -> bool {
+        self.extension_allowed(path) && self.gitignore_allows(path)
+    }
+
+    /// Checks `--include-extensions` and then `--exclude-extensions`,
+    /// ignoring case.
+    fn extension_allowed(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let included = self.args.include_extensions.is_empty()
+            || self.args.include_extensions.contains(&extension);
+        included && !self.args.exclude_extensions.contains(&extension)
+    }
+
+    /// Whether a walked file passes the `--include-gitignored` inversion.
+    fn gitignore_allows(&self, path: &Path) -> bool {
+        !self.only_gitignored() || self.hidden_reason(path) == Some(SkipReason::GitIgnored)
+    }
+
+    /// Why `path` or the nearest directory above it is ignored, if it is
+    /// (`target/` never matches `target/debug/app` directly).
+    fn hidden_reason(&self, path: &Path) -> Option<SkipReason> {
+        let gitignore = self.gitignore_for(path)?;
+        path.ancestors()
+            .find_map(|p| gitignore.ignored_by(p, p != path))
+            .map(Self::skip_reason)
+    }
+
+    fn ignore_reason(&self, path: &Path) -> Option<SkipReason> {
+        self.gitignore_for(path)?
+            .ignored_by(path, path.is_dir())
+            .map(Self::skip_reason)
+    }
+
+    fn skip_reason(source: IgnoreSource) -> SkipReason {
+        match source {
+            IgnoreSource::Gitignore => SkipReason::GitIgnored,
+            IgnoreSource::Custom => SkipReason::CustomIgnored,
+        }
+    }
+
+    /// The rules of the working directory `path` belongs to; other
+    /// directories' .gitignore files never apply to it.
+    fn gitignore_for(&self, path: &Path) -> Option<&GitignoreHelper> {
+        let dir = self.owning_dir(path);
+        self.gitignores
+            .iter()
+            .find(|(root, _)| root == dir)
+            .map(|(_, helper)| helper)
     }
 
-    fn process_single_file(&self, path: &Path) {
-        println!("# File: {}", path.display());
-        match fs::read_to_string(path) {
+    fn process_single_file(
+        &self,
+        path: &Path,
+        contents: &io::Result<String>,
+        sha256: Option<&str>,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        let display_path = self.display_path(path);
+        match self.format {
+            OutputFormat::Ndjson => return OutputFormat::write_ndjson(&display_path, contents, sha256, output),
+            OutputFormat::Html => return OutputFormat::write_html_file(path, &display_path, contents, output),
+            OutputFormat::Org => return OutputFormat::write_org_file(path, &display_path, contents, output),
+            OutputFormat::Rst => return OutputFormat::write_rst_file(path, &display_path, contents, output),
+            OutputFormat::Text => {}
+        }
+
+        let text = contents.as_ref().ok().map(String::as_str);
+        let header_path = match self.symlink_target(path) {
+            Some(target) => format!("{} -> {}", display_path, target.display()),
+            None => display_path.clone(),
+        };
+        let mut header = self.header_template.render(path, &header_path, text);
+        if let Some(sha256) = sha256 {
+            header = format!("{} (sha256: {})", header, sha256);
+        }
+        match self.source_dir(path) {
+            Some(dir) => writeln!(output, "[{}] {}", dir.display(), header)?,
+            None => writeln!(output, "{}", header)?,
+        }
+        match contents {
             Ok(contents) => {
-                println!("{}", contents);
-                println!("\n=====================\n");
+                match self.args.wrap.filter(|&width| width > 0) {
+                    Some(width) => writeln!(output, "{}", Self::wrap_lines(contents, width))?,
+                    None => writeln!(output, "{}", contents)?,
+                }
+                if self.null_separator {
+                    output.write_all(b"\0")?;
+                } else {
+                    writeln!(output, "{}", self.footer_template.render(path, &display_path, text))?;
+                }
+            }
+            Err(_) => writeln!(output, "Error reading file: {}", display_path)?,
+        }
+        Ok(())
+    }
+
+    /// Hex SHA-256 of the raw file bytes when `--sha256` is set, taken
+    /// before any decoding or transformation.
+    fn sha256(&self, contents: &io::Result<Vec<u8>>) -> Option<String> {
+        match contents {
+            Ok(bytes) if self.args.sha256 => Some(format!("{:x}", Sha256::digest(bytes))),
+            _ => None,
+        }
+    }
+
+    /// The resolved target of a symlinked file when `--symlink-target` is set.
+    fn symlink_target(&self, path: &Path) -> Option<PathBuf> {
+        if !self.args.symlink_target || !fs::symlink_metadata(path).is_ok_and(|m| m.is_symlink()) {
+            return None;
+        }
+        fs::canonicalize(path).ok()
+    }
+
+    /// How `path` is shown in the output: relative to `--relative-to` when
+    /// it lies below that base, as collected otherwise.
+    fn display_path(&self, path: &Path) -> String {
+        let (Some(base), Ok(absolute)) = (&self.relative_to, fs::canonicalize(path)) else {
+            return path.display().to_string();
+        };
+        match absolute.strip_prefix(base) {
+            Ok(relative) => relative.display().to_string(),
+            Err(_) => path.display().to_string(),
+        }
+    }
+
+    /// The working directory `path` was collected from, when files from
+    /// several directories are being aggregated.
+    fn source_dir(&self, path: &Path) -> Option<&Path> {
+        (self.working_dirs.len() > 1).then(|| self.owning_dir(path))
+    }
+
+    /// The deepest working directory containing `path`, falling back to the
+    /// main one for paths given relative to it.
+    fn owning_dir(&self, path: &Path) -> &Path {
+        self.working_dirs
+            .iter()
+            .filter(|dir| path.starts_with(dir))
+            .max_by_key(|dir| dir.components().count())
+            .unwrap_or(&self.working_dirs[0])
+    }
+
+    /// Decodes a file and applies the --search filter and the content
+    /// transformations requested on the command line. Returns `None` for
+    /// files that should be left out, recording why in `self.skipped`;
+    /// unreadable files never match a search. The time spent counts as
+    /// processing time.
+    fn prepare_contents(
+        &mut self,
+        path: &Path,
+        contents: io::Result<Vec<u8>>,
+    ) -> Option<io::Result<String>> {
+        let started = Instant::now();
+        let prepared = self.transform_contents(path, contents);
+        self.processing_time += started.elapsed();
+        prepared
+    }
+
+    fn transform_contents(
+        &mut self,
+        path: &Path,
+        contents: io::Result<Vec<u8>>,
+    ) -> Option<io::Result<String>> {
+        if let Ok(bytes) = &contents {
+            if !self.args.no_binary_check && Self::is_binary_file(bytes) {
+                self.log_skip(path, SkipReason::Binary);
+                self.skipped.push((path.to_path_buf(), SkipReason::Binary));
+                return None;
+            }
+        }
+
+        let contents = contents.and_then(|bytes| match (String::from_utf8(bytes), self.encoding_fallback) {
+            (Ok(text), _) => Ok(text),
+            (Err(e), Some(encoding)) => {
+                if enabled!(Level::DEBUG) {
+                    Self::log_action("FALLBACK ENCODING", &format!("{} ({})", path.display(), encoding.name()));
+                }
+                let (text, _) = encoding.decode_without_bom_handling(e.as_bytes());
+                Ok(text.into_owned())
+            }
+            (Err(e), None) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        });
+
+        let contents = contents.map(|contents| match &self.comment_stripper {
+            Some(stripper) => stripper.strip(&contents),
+            None => contents,
+        });
+
+        // Searched after stripping comments, so a match is always in the output
+        let contents = match &self.search {
+            Some(search) => {
+                let found = contents.ok().and_then(|text| match self.args.context_lines {
+                    Some(context) => Self::extract_context(&text, search, context),
+                    None => search.is_match(&text).then_some(text),
+                });
+                match found {
+                    Some(text) => Ok(text),
+                    None => {
+                        self.log_skip(path, SkipReason::ContentMismatch);
+                        self.skipped.push((path.to_path_buf(), SkipReason::ContentMismatch));
+                        return None;
+                    }
+                }
             }
-            Err(_) => println!("Error reading file: {}", path.display()),
+            None => contents,
+        };
+
+        Some(contents.map(|contents| {
+            let mut contents = if self.redactor.is_empty() {
+                contents
+            } else {
+                self.redactor.apply(&contents)
+            };
+            if self.args.normalise_line_endings && contents.contains('\r') {
+                contents = contents.replace("\r\n", "\n").replace('\r', "\n");
+            }
+            if self.args.no_newline_at_eof {
+                // Covers LF, CRLF and old Mac-style CR endings alike
+                let trimmed = contents.trim_end_matches(['\r', '\n']).len();
+                contents.truncate(trimmed);
+            }
+            if self.args.add_language_comment {
+                if let Some(comment) = language_comment(path) {
+                    contents.insert_str(0, &format!("{}\n", comment));
+                }
+            }
+            self.log_include(path, &contents);
+            contents
+        }))
+    }
+
+    fn log_include(&self, path: &Path, contents: &str) {
+        if enabled!(Level::DEBUG) {
+            let details = format!(
+                "{} ({} lines, {} bytes)",
+                path.display(),
+                contents.lines().count(),
+                contents.len()
+            );
+            Self::log_action("INCLUDE", &details);
+        }
+    }
+
+    fn log_skip(&self, path: &Path, reason: SkipReason) {
+        if enabled!(Level::DEBUG) {
+            let label = format!("SKIP {}", reason.label());
+            Self::log_action(&label, &path.display().to_string());
+        }
+    }
+
+    /// Logs a `[LABEL] details` line for a per-file decision.
+    fn log_action(label: &str, details: &str) {
+        debug!("[{}] {}", label, details);
+    }
+
+    async fn print_token_counts(
+        &mut self,
+        mut files: FileStream,
+    ) -> io::Result<usize> {
+        let mut counts: Vec<(PathBuf, usize)> = Vec::new();
+        while let Some((path, contents)) = files.next().await {
+            match self.prepare_contents(&path, contents) {
+                Some(Ok(contents)) => counts.push((path, TokenEstimator::estimate(&contents))),
+                Some(Err(_)) => warn!("Error reading file: {}", path.display()),
+                None => {}
+            }
+        }
+        if counts.is_empty() {
+            return Ok(0);
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "tokens\tpath")?;
+        for (file, tokens) in &counts {
+            writeln!(stdout, "{}\t{}", tokens, self.display_path(file))?;
+        }
+        let total: usize = counts.iter().map(|(_, tokens)| tokens).sum();
+        writeln!(stdout, "{}\ttotal ({} files)", total, counts.len())?;
+        Ok(counts.len())
+    }
+
+    /// Prints size, line count and estimated tokens per file, in
+    /// collection order, followed by the column totals.
+    async fn print_stats(
+        &mut self,
+        mut files: FileStream,
+    ) -> io::Result<usize> {
+        let mut stats: Vec<(PathBuf, usize, usize, usize)> = Vec::new();
+        while let Some((path, contents)) = files.next().await {
+            match self.prepare_contents(&path, contents) {
+                Some(Ok(contents)) => {
+                    let lines = contents.lines().count();
+                    let tokens = TokenEstimator::estimate(&contents);
+                    stats.push((path, contents.len(), lines, tokens));
+                }
+                Some(Err(_)) => warn!("Error reading file: {}", path.display()),
+                None => {}
+            }
+        }
+        if stats.is_empty() {
+            return Ok(0);
         }
+
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "bytes\tlines\ttokens\tpath")?;
+        for (file, bytes, lines, tokens) in &stats {
+            writeln!(stdout, "{}\t{}\t{}\t{}", bytes, lines, tokens, self.display_path(file))?;
+        }
+        let (bytes, lines, tokens) = stats
+            .iter()
+            .fold((0, 0, 0), |(b, l, t), (_, bytes, lines, tokens)| (b + bytes, l + lines, t + tokens));
+        writeln!(stdout, "{}\t{}\t{}\ttotal ({} files)", bytes, lines, tokens, stats.len())?;
+        Ok(stats.len())
+    }
+
+    /// Treats a file as binary if it starts with a known binary signature
+    /// or a NUL byte appears in its first 1024 bytes.
+    fn is_binary_file(bytes: &[u8]) -> bool {
+        magic_bytes::is_known_binary(bytes) || bytes.iter().take(1024).any(|&b| b == 0)
+    }
+
+    /// Breaks lines longer than `width` characters into several lines.
+    /// Counting chars rather than bytes keeps multi-byte characters intact.
+    fn wrap_lines(contents: &str, width: usize) -> String {
+        let mut wrapped = String::with_capacity(contents.len());
+        for line in contents.split_inclusive('\n') {
+            let (text, newline) = match line.strip_suffix('\n') {
+                Some(text) => (text, "\n"),
+                None => (line, ""),
+            };
+            let chars: Vec<char> = text.chars().collect();
+            for (i, chunk) in chars.chunks(width).enumerate() {
+                if i > 0 {
+                    wrapped.push('\n');
+                }
+                wrapped.extend(chunk);
+            }
+            wrapped.push_str(newline);
+        }
+        wrapped
+    }
+
+    /// Keeps only the lines covered by matches of `search` plus `context`
+    /// lines around each, separating disjoint windows with a `...` line.
+    /// Matches may span lines. `None` if nothing matches.
+    fn extract_context(contents: &str, search: &Regex, context: usize) -> Option<String> {
+        let lines: Vec<(usize, &str)> = contents.lines().enumerate().collect();
+        if lines.is_empty() {
+            return None;
+        }
+        // Byte offset at which each line starts, to find a match's lines
+        let line_starts: Vec<usize> = contents
+            .split_inclusive('\n')
+            .scan(0, |offset, line| {
+                let start = *offset;
+                *offset += line.len();
+                Some(start)
+            })
+            .collect();
+        let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+
+        let mut windows: Vec<(usize, usize)> = Vec::new();
+        for found in search.find_iter(contents) {
+            let first = line_of(found.start());
+            let last = line_of(found.end().saturating_sub(1).max(found.start()));
+            let start = first.saturating_sub(context);
+            let end = (last + context).min(lines.len() - 1);
+            match windows.last_mut() {
+                // Merge overlapping or adjacent windows
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => windows.push((start, end)),
+            }
+        }
+        if windows.is_empty() {
+            return None;
+        }
+
+        let mut excerpt = windows
+            .iter()
+            .map(|&(start, end)| {
+                lines[start..=end]
+                    .iter()
+                    .map(|&(_, line)| line)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n...\n");
+        excerpt.push('\n');
+        Some(excerpt)
+    }
+}
+
+/// Files gathered during collection, without duplicates and up to the
+/// `--max-file-count` limit.
+struct CollectedFiles {
+    paths: Vec<PathBuf>,
+    // Canonical paths: overlapping patterns, relative/absolute spellings and
+    // case-insensitive filesystems can all yield the same file twice
+    seen: HashSet<PathBuf>,
+    limit: Option<usize>,
+    // Set once a new match had to be dropped because of the limit
+    truncated: bool,
+}
+
+impl CollectedFiles {
+    fn new(limit: Option<usize>) -> Self {
+        Self { paths: Vec::new(), seen: HashSet::new(), limit, truncated: false }
+    }
+
+    fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Adds `path` unless it was collected before. Duplicates don't count
+    /// towards the limit; a new file beyond it marks the set as truncated.
+    fn push(&mut self, path: PathBuf) {
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if self.seen.contains(&canonical) {
+            return;
+        }
+        if self.limit.is_some_and(|max| self.paths.len() >= max) {
+            self.truncated = true;
+            return;
+        }
+        self.seen.insert(canonical);
+        self.paths.push(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn process_into_writes_to_any_writer() {
+        let dir = std::env::temp_dir().join(format!("agg-files-process-into-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "hello\n").unwrap();
+
+        let args = CliArgs {
+            patterns: vec!["*.txt".to_string()],
+            ..Default::default()
+        };
+        let mut processor = FileProcessor::new(args, dir.clone()).unwrap();
+        let mut output = Vec::new();
+        let result = processor.process_into(&mut output).await;
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result.files_processed, 1);
+        assert_eq!(result.bytes_written, output.len() as u64);
+        assert!(String::from_utf8(output).unwrap().contains("hello"));
+    }
+
+    #[test]
+    fn context_windows_cover_matches_spanning_lines() {
+        let search = RegexBuilder::new("b\\nc").multi_line(true).build().unwrap();
+        let text = "a\nb\nc\nd\ne\nf\n";
+
+        assert_eq!(FileProcessor::extract_context(text, &search, 0).as_deref(), Some("b\nc\n"));
+        assert_eq!(FileProcessor::extract_context(text, &search, 1).as_deref(), Some("a\nb\nc\nd\n"));
+        assert_eq!(FileProcessor::extract_context(text, &Regex::new("x").unwrap(), 1), None);
+    }
+
+    #[tokio::test]
+    async fn benchmark_rejects_a_single_run() {
+        let args = CliArgs {
+            patterns: vec!["*.txt".to_string()],
+            ..Default::default()
+        };
+        let mut processor = FileProcessor::new(args, std::env::temp_dir()).unwrap();
+        let result = processor.benchmark(1).await;
+
+        assert_eq!(result.error_kind, Some(ErrorKind::InvalidArguments));
     }
 }
@@ -0,0 +1,60 @@
+use std::path::Path;
+
+/// A per-file header or footer with `{path}`, `{basename}`, `{ext}`,
+/// `{size}` and `{lines}` placeholders.
+pub struct FileTemplate {
+    template: String,
+}
+
+impl FileTemplate {
+    /// Creates a template, interpreting `\n`, `\t`, `\r`, `\0` and `\\`.
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: Self::unescape(template),
+        }
+    }
+
+    pub fn render(&self, path: &Path, display_path: &str, contents: Option<&str>) -> String {
+        let basename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        let lines = contents.map_or(0, |contents| contents.lines().count());
+
+        self.template
+            .replace("{path}", display_path)
+            .replace("{basename}", &basename)
+            .replace("{ext}", &ext)
+            .replace("{size}", &size.to_string())
+            .replace("{lines}", &lines.to_string())
+    }
+
+    fn unescape(template: &str) -> String {
+        let mut unescaped = String::with_capacity(template.len());
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some('r') => unescaped.push('\r'),
+                Some('0') => unescaped.push('\0'),
+                Some('\\') => unescaped.push('\\'),
+                Some(other) => {
+                    unescaped.push('\\');
+                    unescaped.push(other);
+                }
+                None => unescaped.push('\\'),
+            }
+        }
+        unescaped
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::cli::CliArgs;
+use crate::git_status_handler::{git_available, resolve_paths, run_git};
+
+/// Asks git which files were touched by commits in the history.
+pub struct GitHistoryHandler {
+    repo_dir: PathBuf,
+    author: Option<String>,
+    message_grep: Option<String>,
+}
+
+impl GitHistoryHandler {
+    /// Creates a handler for `repo_dir`, limited to the commits selected by
+    /// `--git-author` and `--git-message-grep` when they are set.
+    pub fn new(repo_dir: &Path, args: &CliArgs) -> Self {
+        Self {
+            repo_dir: repo_dir.to_path_buf(),
+            author: args.git_author.clone(),
+            message_grep: args.git_message_grep.clone(),
+        }
+    }
+
+    /// Files changed by the commits in `since_ref..HEAD`, e.g. `HEAD~5` or a
+    /// release tag, as canonical paths.
+    pub fn get_changed_since_ref(&self, since_ref: &str) -> Result<HashSet<PathBuf>, Box<dyn Error>> {
+        self.committed_files(Some(&format!("{}..HEAD", since_ref)))
+    }
+
+    /// Files changed by any commit reachable from `HEAD`.
+    pub fn get_committed_files(&self) -> Result<HashSet<PathBuf>, Box<dyn Error>> {
+        self.committed_files(None)
+    }
+
+    fn committed_files(&self, range: Option<&str>) -> Result<HashSet<PathBuf>, Box<dyn Error>> {
+        if !git_available() {
+            return Ok(HashSet::new());
+        }
+        // -z keeps git from quoting unusual paths such as non-ASCII names
+        let mut args = vec!["log", "-z", "--name-only", "--relative", "--pretty=format:"];
+        // git matches --author as a substring of "Name <email>"
+        let author = self.author.as_ref().map(|author| format!("--author={}", author));
+        args.extend(author.as_deref());
+        let grep = self.message_grep.as_ref().map(|pattern| format!("--grep={}", pattern));
+        args.extend(grep.as_deref());
+        if let Some(range) = range {
+            args.extend(["--end-of-options", range]);
+        }
+
+        let output = run_git(&self.repo_dir, &args)?;
+        Ok(resolve_paths(&self.repo_dir, &output))
+    }
+}
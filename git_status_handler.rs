@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// What `git status` reports for a working tree, as canonical paths.
+#[derive(Default)]
+pub struct GitChanges {
+    /// Files with staged, unstaged or untracked changes.
+    pub changed_files: HashSet<PathBuf>,
+    /// The subset of `changed_files` that git doesn't track yet.
+    pub untracked_files: HashSet<PathBuf>,
+    /// Files deleted from the working tree or the index, relative to the
+    /// handler's directory. Files outside that directory are left out.
+    pub deleted_files: Vec<PathBuf>,
+}
+
+/// Asks git which files in a working tree are affected by local changes.
+pub struct GitStatusHandler {
+    repo_dir: PathBuf,
+    git_available: bool,
+}
+
+impl GitStatusHandler {
+    pub fn new(repo_dir: &Path) -> Self {
+        Self {
+            repo_dir: repo_dir.to_path_buf(),
+            git_available: git_available(),
+        }
+    }
+
+    /// Files touched by the most recent stash entry (`stash@{0}`), as
+    /// canonical paths so they can be compared with collected files.
+    pub fn stashed_files(&self) -> Result<HashSet<PathBuf>, Box<dyn Error>> {
+        if !self.git_available {
+            return Ok(HashSet::new());
+        }
+        let list = self.git(&["stash", "list", "-n", "1"])?;
+        if list.trim().is_empty() {
+            return Err("No git stash found".into());
+        }
+
+        let output = self.git(&["stash", "show", "-z", "--name-only", "--relative", "stash@{0}"])?;
+        Ok(resolve_paths(&self.repo_dir, &output))
+    }
+
+    /// Files with local changes. Renamed files are reported under their
+    /// new name, with the original name counted as deleted; deleted files
+    /// are listed separately since there is nothing on disk to read.
+    pub fn changes(&self) -> Result<GitChanges, Box<dyn Error>> {
+        if !self.git_available {
+            return Ok(GitChanges::default());
+        }
+        let root = PathBuf::from(self.git(&["rev-parse", "--show-toplevel"])?.trim_end());
+        let repo_dir = fs::canonicalize(&self.repo_dir)?;
+        // -z avoids git's quoting of unusual paths, which are always
+        // relative to the repository root in porcelain output
+        let output = self.git(&["status", "--porcelain", "-z", "--untracked-files=all"])?;
+
+        let mut changes = GitChanges::default();
+        // Original paths carry no status, so they may be as short as one byte
+        let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+        while let Some(entry) = entries.next() {
+            let Some((status, path)) = entry.split_at_checked(3) else {
+                continue;
+            };
+            // Renames and copies are followed by an extra entry holding the
+            // original path, which only a rename removes
+            if status.contains(['R', 'C']) {
+                let original = entries.next().map(|original| root.join(original));
+                if let Some(original) = original.filter(|_| status.contains('R')) {
+                    if let Ok(original) = original.strip_prefix(&repo_dir) {
+                        changes.deleted_files.push(original.to_path_buf());
+                    }
+                }
+            }
+            if status.contains('D') {
+                if let Ok(path) = root.join(path).strip_prefix(&repo_dir) {
+                    changes.deleted_files.push(path.to_path_buf());
+                }
+                continue;
+            }
+            let path = root.join(path);
+            let path = fs::canonicalize(&path).unwrap_or(path);
+            if status.starts_with("??") {
+                changes.untracked_files.insert(path.clone());
+            }
+            changes.changed_files.insert(path);
+        }
+        Ok(changes)
+    }
+
+    /// The contents of `path` (relative to the handler's directory) as of
+    /// `HEAD`.
+    pub fn head_contents(&self, path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+        let spec = format!("HEAD:./{}", path.display());
+        Ok(self.git(&["show", &spec])?.into_bytes())
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        run_git(&self.repo_dir, args)
+    }
+}
+
+/// Whether a `git` executable can be run, checked once per process. When
+/// it can't, the handlers report no files and a single warning says why.
+pub(crate) fn git_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let found = Command::new("git")
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !found {
+            warn!("git was not found in PATH; git-based filters will match no files");
+        }
+        found
+    })
+}
+
+/// Runs git in `repo_dir` and returns its stdout, turning a non-zero exit
+/// into an error carrying git's stderr.
+pub(crate) fn run_git(repo_dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args.join(" "), stderr.trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Turns NUL-separated paths relative to `base` (git's `-z` output) into
+/// canonical paths.
+pub(crate) fn resolve_paths(base: &Path, output: &str) -> HashSet<PathBuf> {
+    output
+        .split('\0')
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let path = base.join(line);
+            fs::canonicalize(&path).unwrap_or(path)
+        })
+        .collect()
+}
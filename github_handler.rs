@@ -1,30 +1,69 @@
-use reqwest;
+use reqwest::StatusCode;
 use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::fs;
 use flate2::read::GzDecoder;
+use futures::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
 use tar::Archive;
+use tracing::warn;
 use url::Url;
 
+use crate::cli::CliArgs;
+use crate::temp_manager::{TempManager, COMPLETE_MARKER};
+
+/// REST API version requested unless `--github-api-version` says otherwise.
+const DEFAULT_API_VERSION: &str = "2022-11-28";
+
 pub struct RepoInfo {
+    pub host: String,
     pub owner: String,
     pub repo: String,
     pub branch: String,
+    /// Commit to download instead of the tip of `branch` (`--git-commit`).
+    pub commit: Option<String>,
     pub path: Option<String>,
+    /// Local `.tar.gz` to extract instead of downloading (`file://` URLs).
+    pub archive: Option<PathBuf>,
 }
 
 pub struct GitHubHandler {
     client: reqwest::Client,
+    host: Option<String>,
+    branch: Option<String>,
+    commit: Option<String>,
+    path: Option<String>,
+    retries: u32,
+    retry_delay: Duration,
+    progress: bool,
+    api_version: String,
 }
 
 impl GitHubHandler {
-    pub fn new() -> Self {
+    pub fn new(args: &CliArgs) -> Self {
         Self {
             client: reqwest::Client::new(),
+            host: args.github_host.clone(),
+            branch: args.branch.clone(),
+            commit: args.git_commit.clone(),
+            path: args.path.clone(),
+            retries: args.retry.unwrap_or(3),
+            retry_delay: Duration::from_millis(args.retry_delay.unwrap_or(1000)),
+            progress: args.progress && !args.quiet,
+            api_version: args
+                .github_api_version
+                .clone()
+                .unwrap_or_else(|| DEFAULT_API_VERSION.to_string()),
         }
     }
 
     pub fn parse_url(&self, url: &str) -> Result<RepoInfo, Box<dyn Error>> {
         let parsed_url = Url::parse(url)?;
+        if parsed_url.scheme() == "file" {
+            return self.parse_archive_url(&parsed_url);
+        }
+
         let path_segments: Vec<&str> = parsed_url.path_segments()
             .ok_or("Invalid URL")?
             .collect();
@@ -33,10 +72,16 @@ impl GitHubHandler {
             return Err("Invalid GitHub URL".into());
         }
 
+        // An explicit --github-host wins over the host in the URL
+        let host = match &self.host {
+            Some(host) => host.clone(),
+            None => parsed_url.host_str().unwrap_or("github.com").to_string(),
+        };
+
         let owner = path_segments[0].to_string();
         let repo = path_segments[1].to_string();
         
-        let (branch, path) = if path_segments.len() > 3 && path_segments[2] == "tree" {
+        let (mut branch, path) = if path_segments.len() > 3 && path_segments[2] == "tree" {
             let branch = path_segments[3].to_string();
             let path = if path_segments.len() > 4 {
                 Some(path_segments[4..].join("/"))
@@ -48,35 +93,173 @@ impl GitHubHandler {
             ("main".to_string(), None)
         };
 
+        if let Some(branch_override) = &self.branch {
+            if path_segments.len() > 3 && path_segments[2] == "tree" {
+                warn!(
+                    "--branch '{}' overrides the branch '{}' in the URL; \
+                     embedding the branch in the URL is deprecated when --branch is used",
+                    branch_override, branch
+                );
+            }
+            branch = branch_override.clone();
+        }
+
         Ok(RepoInfo {
+            host,
             owner,
             repo,
             branch,
-            path,
+            commit: self.commit.clone(),
+            path: self.override_path(path),
+            archive: None,
         })
     }
 
-    pub async fn download_repository(&self, repo_info: &RepoInfo) -> Result<(), Box<dyn Error>> {
-        let temp_manager = crate::temp_manager::TempManager::new();
-        let target_dir = temp_manager.get_repo_path(repo_info);
+    fn parse_archive_url(&self, url: &Url) -> Result<RepoInfo, Box<dyn Error>> {
+        let archive = url.to_file_path().map_err(|_| "Invalid file URL")?;
+        let file_name = archive
+            .file_name()
+            .ok_or("File URL does not name an archive")?
+            .to_string_lossy();
+        let name = file_name
+            .trim_end_matches(".tar.gz")
+            .trim_end_matches(".tgz")
+            .to_string();
 
-        // Create target directory if it doesn't exist
-        fs::create_dir_all(&target_dir).await?;
+        Ok(RepoInfo {
+            host: "local".to_string(),
+            owner: "local".to_string(),
+            repo: name,
+            branch: "archive".to_string(),
+            commit: None,
+            path: self.override_path(None),
+            archive: Some(archive),
+        })
+    }
+
+    fn override_path(&self, path: Option<String>) -> Option<String> {
+        match &self.path {
+            Some(path_override) => {
+                let trimmed = path_override.trim_matches('/');
+                (!trimmed.is_empty()).then(|| trimmed.to_string())
+            }
+            None => path,
+        }
+    }
+
+    /// GitHub Enterprise Server serves the REST API under `/api/v3` on the
+    /// instance's own host rather than on a separate `api.` subdomain.
+    fn api_base(host: &str) -> String {
+        if host == "github.com" || host == "www.github.com" {
+            "https://api.github.com".to_string()
+        } else {
+            format!("https://{}/api/v3", host)
+        }
+    }
+
+    /// Requests `url`, retrying connection errors and transient status
+    /// codes with a fixed delay. Other failures are returned immediately.
+    async fn send_with_retry(&self, url: &str) -> Result<reqwest::Response, Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            let result = self.client
+                .get(url)
+                .header("User-Agent", "rust-file-finder")
+                .header("X-GitHub-Api-Version", &self.api_version)
+                .send()
+                .await;
+
+            let reason = match result {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) if Self::is_transient(response.status()) => {
+                    format!("GitHub returned {}", response.status())
+                }
+                Ok(response) => {
+                    return Err(format!("GitHub returned {} for {}", response.status(), url).into());
+                }
+                Err(e) if e.is_connect() || e.is_timeout() || e.is_request() => e.to_string(),
+                Err(e) => return Err(e.into()),
+            };
+
+            if attempt >= self.retries {
+                return Err(format!("{} (gave up after {} attempts)", reason, attempt + 1).into());
+            }
+            attempt += 1;
+            warn!(
+                "{}; retrying in {}ms ({}/{})",
+                reason, self.retry_delay.as_millis(), attempt, self.retries
+            );
+            tokio::time::sleep(self.retry_delay).await;
+        }
+    }
 
-        // Download tarball
+    async fn download_with_progress(response: reqwest::Response) -> Result<Vec<u8>, Box<dyn Error>> {
+        let total = response.content_length();
+        let progress = match total {
+            Some(total) => ProgressBar::new(total).with_style(
+                ProgressStyle::with_template(
+                    "{bar:40} {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+                )?,
+            ),
+            // GitHub usually streams tarballs without a Content-Length
+            None => ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{spinner} {bytes} ({bytes_per_sec})")?,
+            ),
+        };
+
+        let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            bytes.extend_from_slice(&chunk);
+            progress.set_position(bytes.len() as u64);
+        }
+        progress.finish();
+
+        Ok(bytes)
+    }
+
+    fn is_transient(status: StatusCode) -> bool {
+        matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+    }
+
+    async fn download_tarball(&self, repo_info: &RepoInfo) -> Result<Vec<u8>, Box<dyn Error>> {
+        // The tarball endpoint accepts a commit SHA wherever it takes a branch
+        let reference = repo_info.commit.as_ref().unwrap_or(&repo_info.branch);
         let url = format!(
-            "https://api.github.com/repos/{}/{}/tarball/{}",
-            repo_info.owner, repo_info.repo, repo_info.branch
+            "{}/repos/{}/{}/tarball/{}",
+            Self::api_base(&repo_info.host),
+            repo_info.owner,
+            repo_info.repo,
+            reference
         );
 
-        let response = self.client
-            .get(&url)
-            .header("User-Agent", "rust-file-finder")
-            .send()
-            .await?;
+        let response = self.send_with_retry(&url).await?;
+
+        if self.progress {
+            Self::download_with_progress(response).await
+        } else {
+            Ok(response.bytes().await?.to_vec())
+        }
+    }
+
+    pub async fn download_repository(&self, repo_info: &RepoInfo) -> Result<(), Box<dyn Error>> {
+        let temp_manager = TempManager::new();
+        let target_dir = temp_manager.get_repo_path(repo_info);
+
+        // Discard whatever an interrupted earlier download left behind
+        if target_dir.exists() {
+            fs::remove_dir_all(&target_dir).await?;
+        }
+        fs::create_dir_all(&target_dir).await?;
+
+        let bytes = match &repo_info.archive {
+            Some(archive) => fs::read(archive)
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", archive.display(), e))?,
+            None => self.download_tarball(repo_info).await?,
+        };
 
-        let bytes = response.bytes().await?;
-        
         // Extract tarball
         let decoder = GzDecoder::new(&bytes[..]);
         let mut archive = Archive::new(decoder);
@@ -88,30 +271,38 @@ impl GitHubHandler {
         // Extract files
         archive.unpack(&temp_dir)?;
 
-        // Move files from the extracted directory to the target directory
-        let extracted_dir = std::fs::read_dir(&temp_dir)?
-            .next()
-            .ok_or("No files extracted")??.path();
+        // GitHub tarballs wrap everything in a single top-level directory;
+        // local archives may not
+        let entries: Vec<_> = std::fs::read_dir(&temp_dir)?.collect::<Result<_, _>>()?;
+        let extracted_dir = match entries.as_slice() {
+            [] => return Err("No files extracted".into()),
+            [entry] if entry.path().is_dir() => entry.path(),
+            _ => temp_dir.clone(),
+        };
 
-        if let Some(path) = &repo_info.path {
-            let source_dir = extracted_dir.join(path);
-            if source_dir.exists() {
-                std::fs::rename(source_dir, &target_dir)?;
-            } else {
-                return Err(format!("Path '{}' not found in repository", path).into());
-            }
-        } else {
-            // Move all files from extracted directory to target directory
-            for entry in std::fs::read_dir(extracted_dir)? {
-                let entry = entry?;
-                let target_path = target_dir.join(entry.file_name());
-                std::fs::rename(entry.path(), target_path)?;
-            }
+        let source_dir = match &repo_info.path {
+            Some(path) => extracted_dir.join(path),
+            None => extracted_dir,
+        };
+        if !source_dir.is_dir() {
+            std::fs::remove_dir_all(&temp_dir)?;
+            let path = repo_info.path.as_deref().unwrap_or_default();
+            return Err(format!("Path '{}' not found in repository", path).into());
+        }
+
+        // Move the entries rather than the directory itself: target_dir
+        // already exists and still contains the temp directory
+        for entry in std::fs::read_dir(source_dir)? {
+            let entry = entry?;
+            let target_path = target_dir.join(entry.file_name());
+            std::fs::rename(entry.path(), target_path)?;
         }
 
         // Clean up temporary directory
         std::fs::remove_dir_all(temp_dir)?;
 
+        std::fs::write(target_dir.join(COMPLETE_MARKER), "")?;
+
         Ok(())
     }
 }
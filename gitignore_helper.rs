@@ -1,14 +1,248 @@
 use ignore::gitignore::{GitignoreBuilder, Gitignore};
+use ignore::WalkBuilder;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
 
-pub struct GitignoreHelper;
+/// Which kind of rule excludes a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoreSource {
+    /// A `.gitignore` file.
+    Gitignore,
+    /// The extra ignore file or a raw pattern (`--ignore-pattern`).
+    Custom,
+}
+
+/// Answers whether paths are excluded by `.gitignore` rules.
+pub struct GitignoreHelper {
+    // Ordered deepest directory first so nested rules take precedence
+    gitignores: Vec<Gitignore>,
+    // Extra ignore file and raw patterns, checked after the .gitignore files
+    custom: Vec<Gitignore>,
+    // Subtrees where .gitignore files are not consulted
+    exempt_dirs: Vec<PathBuf>,
+}
 
 impl GitignoreHelper {
-    pub fn build() -> Option<Gitignore> {
-        let mut builder = GitignoreBuilder::new(".");
-        if builder.add(".gitignore").is_none() {
-            builder.build().ok()
-        } else {
-            None
+    /// Loads every `.gitignore` below `root`.
+    pub fn build(root: &Path) -> Option<Self> {
+        Self::builder(root).build()
+    }
+
+    /// Starts configuring a helper whose rules are rooted at `root`.
+    pub fn builder(root: &Path) -> GitignoreHelperBuilder {
+        GitignoreHelperBuilder {
+            root: root.to_path_buf(),
+            gitignore_path: None,
+            ignore_path: None,
+            patterns: Vec::new(),
+            exempt_dirs: Vec::new(),
+            skip_gitignore_files: false,
+        }
+    }
+
+    fn discover(root: &Path) -> Vec<Gitignore> {
+        let mut gitignores = Vec::new();
+
+        // The walker itself honours .gitignore files, so directories that a
+        // parent already excludes are never searched for nested ones
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .git_global(false)
+            .git_exclude(false)
+            .require_git(false)
+            .filter_entry(|e| e.file_name() != ".git")
+            .build();
+
+        for entry in walker.flatten() {
+            let path = entry.path();
+            if entry.file_name() != ".gitignore" || !path.is_file() {
+                continue;
+            }
+
+            let dir = Self::normalize(path.parent().unwrap_or(root));
+            gitignores.extend(Self::load(&dir, path));
+        }
+
+        gitignores
+    }
+
+    /// Parses a single ignore file whose rules are relative to `dir`.
+    /// Malformed lines are reported and skipped; a file that can't be
+    /// built at all contributes no rules.
+    fn load(dir: &Path, path: &Path) -> Option<Gitignore> {
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(e) = builder.add(path) {
+            // A missing file is not worth a warning; the caller may be guessing
+            if e.is_io() && !path.exists() {
+                return None;
+            }
+            // The error already names the file and line
+            warn!("Skipping invalid ignore rules: {}", e);
         }
+        match builder.build() {
+            Ok(gitignore) => Some(gitignore),
+            Err(e) => {
+                warn!("Ignoring {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Whether the nearest matching rule for `path` ignores it.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.ignored_by(path, is_dir).is_some()
+    }
+
+    /// The kind of rule that ignores `path`, if the nearest matching one
+    /// does.
+    pub fn ignored_by(&self, path: &Path, is_dir: bool) -> Option<IgnoreSource> {
+        let path = Self::normalize(path);
+
+        if !self.exempt_dirs.iter().any(|dir| path.starts_with(dir)) {
+            if let Some(ignored) = Self::first_match(&self.gitignores, &path, is_dir) {
+                return ignored.then_some(IgnoreSource::Gitignore);
+            }
+        }
+        Self::first_match(&self.custom, &path, is_dir)
+            .unwrap_or(false)
+            .then_some(IgnoreSource::Custom)
+    }
+
+    /// `Some(true)` if the first matching rule ignores `path`, `Some(false)`
+    /// if it whitelists it, `None` if no rule matches.
+    fn first_match(gitignores: &[Gitignore], path: &Path, is_dir: bool) -> Option<bool> {
+        for gitignore in gitignores {
+            if !path.starts_with(gitignore.path()) {
+                continue;
+            }
+            let matched = gitignore.matched(path, is_dir);
+            if matched.is_ignore() {
+                return Some(true);
+            }
+            if matched.is_whitelist() {
+                return Some(false);
+            }
+        }
+        None
+    }
+
+    /// Drops `.` components so `./src/a.rs` and `src/a.rs` compare equal.
+    fn normalize(path: &Path) -> PathBuf {
+        path.components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect()
+    }
+}
+
+/// Configures where a [`GitignoreHelper`] takes its rules from.
+pub struct GitignoreHelperBuilder {
+    root: PathBuf,
+    gitignore_path: Option<PathBuf>,
+    ignore_path: Option<PathBuf>,
+    patterns: Vec<String>,
+    exempt_dirs: Vec<PathBuf>,
+    skip_gitignore_files: bool,
+}
+
+impl GitignoreHelperBuilder {
+    /// Reads this file as the root gitignore instead of discovering every
+    /// `.gitignore` under the root.
+    pub fn gitignore_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.gitignore_path = Some(path.into());
+        self
+    }
+
+    /// Reads an additional ignore file whose rules apply from the root.
+    pub fn ignore_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ignore_path = Some(path.into());
+        self
+    }
+
+    /// Adds a raw gitignore-syntax pattern applying from the root.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    /// Skips `.gitignore` rules for paths below `dir` (relative to the
+    /// root); the ignore file and raw patterns still apply there.
+    pub fn exempt_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.exempt_dirs.push(self.root.join(dir));
+        self
+    }
+
+    /// Reads no `.gitignore` files at all (`-i`); only the ignore file and
+    /// raw patterns apply.
+    pub fn skip_gitignore_files(mut self) -> Self {
+        self.skip_gitignore_files = true;
+        self
+    }
+
+    /// Loads the configured rules; `None` if there are none at all.
+    pub fn build(self) -> Option<GitignoreHelper> {
+        let root = GitignoreHelper::normalize(&self.root);
+        let mut gitignores: Vec<Gitignore> = match &self.gitignore_path {
+            _ if self.skip_gitignore_files => Vec::new(),
+            Some(path) => GitignoreHelper::load(&root, path).into_iter().collect(),
+            None => GitignoreHelper::discover(&self.root),
+        };
+
+        let mut custom: Vec<Gitignore> = Vec::new();
+        if let Some(path) = &self.ignore_path {
+            custom.extend(GitignoreHelper::load(&root, path));
+        }
+
+        if !self.patterns.is_empty() {
+            let mut builder = GitignoreBuilder::new(&root);
+            for pattern in &self.patterns {
+                if let Err(e) = builder.add_line(None, pattern) {
+                    warn!("Ignoring invalid pattern '{}': {}", pattern, e);
+                }
+            }
+            match builder.build() {
+                Ok(gitignore) => custom.push(gitignore),
+                Err(e) => warn!("Ignoring --ignore-pattern rules: {}", e),
+            }
+        }
+
+        if gitignores.is_empty() && custom.is_empty() {
+            return None;
+        }
+
+        gitignores.sort_by_key(|gi| std::cmp::Reverse(gi.path().components().count()));
+        let exempt_dirs = self.exempt_dirs.iter().map(|dir| GitignoreHelper::normalize(dir)).collect();
+        Some(GitignoreHelper { gitignores, custom, exempt_dirs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_patterns_apply_from_the_root() {
+        let helper = GitignoreHelper::builder(Path::new("/repo"))
+            .gitignore_path("/repo/missing.gitignore")
+            .pattern("*.log")
+            .pattern("!keep.log")
+            .build()
+            .unwrap();
+
+        assert!(helper.is_ignored(Path::new("/repo/debug.log"), false));
+        assert!(helper.is_ignored(Path::new("/repo/logs/debug.log"), false));
+        assert!(!helper.is_ignored(Path::new("/repo/keep.log"), false));
+        assert!(!helper.is_ignored(Path::new("/repo/main.rs"), false));
+    }
+
+    #[test]
+    fn raw_patterns_apply_without_gitignore_files() {
+        let helper = GitignoreHelper::builder(Path::new("/repo"))
+            .skip_gitignore_files()
+            .pattern("*.log")
+            .build()
+            .unwrap();
+
+        assert_eq!(helper.ignored_by(Path::new("/repo/x.log"), false), Some(IgnoreSource::Custom));
+        assert_eq!(helper.ignored_by(Path::new("/repo/x.rs"), false), None);
     }
 }
@@ -0,0 +1,80 @@
+//! Aggregate the contents of many files into a single text stream.
+//!
+//! The `agg-files` binary is a thin wrapper around this crate. Embedders
+//! build a [`CliArgs`] (directly or via [`CliArgs::parse`]), hand it to a
+//! [`FileProcessor`] and inspect the returned [`ProcessResult`].
+
+#![warn(missing_docs)]
+
+/// Command-line options and their parsing.
+pub mod cli;
+/// System clipboard access for `--clipboard`.
+pub mod clipboard;
+mod comment_stripper;
+mod config;
+/// Commands run when processing finishes.
+pub mod exit_hooks;
+/// Collecting files and writing the aggregated output.
+pub mod file_processor;
+mod file_template;
+mod git_history_handler;
+mod git_status_handler;
+mod github_handler;
+/// `.gitignore` and custom ignore rules.
+pub mod gitignore_helper;
+mod magic_bytes;
+mod manifest;
+/// Output formats and compression.
+pub mod output_format;
+/// Glob-to-regex translation for file patterns.
+pub mod pattern_matcher;
+mod redactor;
+mod shell;
+mod temp_manager;
+mod token_estimator;
+/// Build and version information.
+pub mod version;
+
+pub use cli::CliArgs;
+pub use exit_hooks::ExitHooks;
+pub use file_processor::{ErrorKind, FileProcessor, ProcessResult, SkipReason};
+pub use gitignore_helper::{GitignoreHelper, GitignoreHelperBuilder, IgnoreSource};
+pub use output_format::{Compression, OutputFormat};
+pub use pattern_matcher::PatternMatcher;
+pub use version::Version;
+
+use github_handler::GitHubHandler;
+use std::error::Error;
+use std::path::PathBuf;
+use temp_manager::TempManager;
+
+/// One-line summary of the download cache: its directory, the number of
+/// cached repositories and their total size.
+pub fn cache_summary() -> String {
+    let temp_manager = TempManager::new();
+    format!(
+        "{} ({} repositories, {} bytes)",
+        temp_manager.base_dir().display(),
+        temp_manager.cached_repo_count(),
+        temp_manager.total_cache_size()
+    )
+}
+
+/// Downloads the repository behind a GitHub `url` into the local cache
+/// (unless it is already there) and returns the directory to process.
+///
+/// `file://` URLs pointing at a local `.tar.gz` are extracted instead.
+pub async fn process_github_url(url: &str, args: &CliArgs) -> Result<PathBuf, Box<dyn Error>> {
+    let github_handler = GitHubHandler::new(args);
+    let repo_info = github_handler.parse_url(url)?;
+    
+    let temp_manager = TempManager::new();
+    let repo_path = temp_manager.get_repo_path(&repo_info);
+
+    // Local archives are cheap to extract and may have changed since last time
+    if repo_info.archive.is_some() || !temp_manager.repo_exists(&repo_info) {
+        github_handler.download_repository(&repo_info).await?;
+    }
+
+    Ok(repo_path)
+}
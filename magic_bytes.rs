@@ -0,0 +1,44 @@
+/// Leading bytes of common binary formats. Only signatures containing bytes
+/// that never start a text file are listed; printable ones (`MZ`, `ID3`,
+/// `%PDF-`, `GIF89a`, ...) are left to the NUL scan.
+const SIGNATURES: &[&[u8]] = &[
+    b"\x7fELF",            // ELF executables and libraries
+    b"\xca\xfe\xba\xbe",   // Mach-O fat binaries, Java classes
+    b"\xcf\xfa\xed\xfe",   // Mach-O 64-bit
+    b"\xce\xfa\xed\xfe",   // Mach-O 32-bit
+    b"\0asm",              // WebAssembly
+    b"\x89PNG\r\n\x1a\n",  // PNG
+    b"\xff\xd8\xff",       // JPEG
+    b"PK\x03\x04",         // ZIP, JAR, DOCX, ...
+    b"\x1f\x8b",           // gzip
+    b"\xfd7zXZ\0",         // xz
+    b"7z\xbc\xaf\x27\x1c",
+    b"\x28\xb5\x2f\xfd",   // zstd
+    b"Rar!\x1a\x07",
+    b"SQLite format 3\0",
+];
+
+/// PDF header. Printable, so a file starting with it only counts as a PDF
+/// if it also has a non-ASCII byte early on; real PDFs put a binary
+/// comment on the second line.
+const PDF_SIGNATURE: &[u8] = b"%PDF-";
+
+/// Whether `bytes` starts with the signature of a known binary format.
+pub fn is_known_binary(bytes: &[u8]) -> bool {
+    SIGNATURES.iter().any(|signature| bytes.starts_with(signature))
+        || (bytes.starts_with(PDF_SIGNATURE) && !bytes.iter().take(1024).all(u8::is_ascii))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_known_binary;
+
+    #[test]
+    fn text_starting_like_a_printable_signature_is_kept() {
+        assert!(!is_known_binary(b"ID3 tag parsing notes"));
+        assert!(!is_known_binary(b"BZh is the bzip2 magic"));
+        assert!(is_known_binary(b"\x7fELF\x02\x01"));
+        assert!(!is_known_binary(b"%PDF-1.7 is the version we target"));
+        assert!(is_known_binary(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n1 0 obj"));
+    }
+}
@@ -1,58 +1,117 @@
-mod cli;
-mod file_processor;
-mod gitignore_helper;
-mod pattern_matcher;
-mod github_handler;
-mod temp_manager;
-mod version;
-
-use cli::CliArgs;
-use file_processor::FileProcessor;
-use github_handler::GitHubHandler;
-use temp_manager::TempManager;
+use agg_files::{
+    cache_summary, clipboard, process_github_url, CliArgs, ErrorKind, ExitHooks, FileProcessor, ProcessResult,
+    Version,
+};
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
-use version::Version;
+use std::process;
+use tracing::{error, Level};
 
 #[tokio::main]
 async fn main() {
-    let args = CliArgs::parse();
+    if std::env::args().nth(1).as_deref() == Some(clipboard::DAEMON_ARG) {
+        if clipboard::serve().is_err() {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let args = match CliArgs::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(ErrorKind::InvalidArguments.exit_code());
+        }
+    };
     
     if args.show_version {
         Version::print();
         return;
     }
 
+    if args.cache_info {
+        println!("{}", cache_summary());
+        return;
+    }
+
     if !args.is_valid() {
         args.print_usage();
         return;
     }
 
+    if let Err(e) = args.validate() {
+        eprintln!("Error: {}", e);
+        process::exit(ErrorKind::InvalidArguments.exit_code());
+    }
+
+    init_logging(&args);
+    let hooks = ExitHooks::new(&args);
+
     let working_dir = if let Some(url) = &args.github_url {
-        match process_github_url(url).await {
+        match process_github_url(url, &args).await {
             Ok(dir) => dir,
             Err(e) => {
-                eprintln!("Error processing GitHub URL: {}", e);
-                return;
+                error!("Error processing GitHub URL: {}", e);
+                exit(&hooks, ErrorKind::Network.exit_code(), &ProcessResult::default());
             }
         }
     } else {
         PathBuf::from(".")
     };
 
-    let processor = FileProcessor::new(args, working_dir);
-    processor.process();
-}
+    let benchmark = args.benchmark;
+    let to_stdout = args.pipe_to.is_none();
+    let mut processor = match FileProcessor::new(args, working_dir) {
+        Ok(processor) => processor,
+        Err(e) => {
+            error!("{}", e);
+            exit(&hooks, ErrorKind::InvalidArguments.exit_code(), &ProcessResult::default());
+        }
+    };
 
-async fn process_github_url(url: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let github_handler = GitHubHandler::new();
-    let repo_info = github_handler.parse_url(url)?;
-    
-    let temp_manager = TempManager::new();
-    let repo_path = temp_manager.get_repo_path(&repo_info);
+    let result = match benchmark {
+        Some(runs) => processor.benchmark(runs).await,
+        None => processor.process().await,
+    };
 
-    if !temp_manager.repo_exists(&repo_info) {
-        github_handler.download_repository(&repo_info).await?;
+    // A closed stdout (e.g. `| head`) is not worth reporting; --pipe-to
+    // deals with its own command
+    if let Some(e) = &result.error {
+        if to_stdout && e.kind() == std::io::ErrorKind::BrokenPipe {
+            exit(&hooks, 0, &result);
+        }
+        error!("Error writing output: {}", e);
     }
 
-    Ok(repo_path)
+    exit(&hooks, result.exit_code(), &result);
+}
+
+/// Runs the matching exit hook, then exits with `code`.
+fn exit(hooks: &ExitHooks, code: i32, result: &ProcessResult) -> ! {
+    hooks.run(code, result);
+    process::exit(code);
+}
+
+/// Sends log events to stderr at the level picked by `--log-level`, or by
+/// `--verbose`/`--quiet` when it isn't given.
+fn init_logging(args: &CliArgs) {
+    let level = match args.log_level.as_deref() {
+        Some(level) => level.parse().unwrap_or(Level::INFO),
+        None if args.verbose => Level::DEBUG,
+        None if args.quiet => Level::WARN,
+        None => Level::INFO,
+    };
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_target(false)
+        .without_time();
+
+    if args.log_format.as_deref() == Some("json") {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
 }
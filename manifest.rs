@@ -0,0 +1,114 @@
+use chrono::Utc;
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// File name of the `--manifest` sidecar, written to the current directory.
+const MANIFEST_FILE: &str = "agg-manifest.json";
+
+struct ManifestEntry {
+    path: String,
+    size: usize,
+    lines: usize,
+    sha256: String,
+}
+
+/// Records what a run wrote so it can be checked or reproduced later.
+pub(crate) struct Manifest {
+    output: String,
+    output_bytes: u64,
+    files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Starts a manifest for output going to `output` (e.g. `stdout`).
+    pub fn new(output: String) -> Self {
+        Self {
+            output,
+            output_bytes: 0,
+            files: Vec::new(),
+        }
+    }
+
+    /// Records an included file: its size and hash on disk, and the number
+    /// of lines written for it.
+    pub fn add(&mut self, path: String, size: usize, sha256: String, contents: &str) {
+        self.files.push(ManifestEntry {
+            path,
+            size,
+            lines: contents.lines().count(),
+            sha256,
+        });
+    }
+
+    pub fn add_output_bytes(&mut self, bytes: u64) {
+        self.output_bytes += bytes;
+    }
+
+    pub fn write(&self, working_dirs: &[PathBuf]) -> io::Result<()> {
+        let files: Vec<_> = self
+            .files
+            .iter()
+            .map(|file| {
+                json!({
+                    "path": file.path,
+                    "size": file.size,
+                    "lines": file.lines,
+                    "sha256": file.sha256,
+                })
+            })
+            .collect();
+        let manifest = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "command_line": env::args().collect::<Vec<_>>(),
+            "working_dirs": working_dirs.iter().map(|dir| absolute(dir)).collect::<Vec<_>>(),
+            "timestamp": Utc::now().to_rfc3339(),
+            "outputs": [{
+                "target": self.output,
+                "bytes": self.output_bytes,
+                "files": self.files.len(),
+            }],
+            "files": files,
+        });
+
+        let mut text = serde_json::to_string_pretty(&manifest).map_err(io::Error::other)?;
+        text.push('\n');
+        // A manifest cut short (e.g. by a full disk) is worse than none
+        fs::write(MANIFEST_FILE, text).inspect_err(|_| {
+            let _ = fs::remove_file(MANIFEST_FILE);
+        })
+    }
+}
+
+fn absolute(dir: &Path) -> String {
+    fs::canonicalize(dir)
+        .unwrap_or_else(|_| dir.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// Passes writes through while counting the bytes written.
+pub(crate) struct CountingWriter<'a, W> {
+    inner: &'a mut W,
+    pub bytes: u64,
+}
+
+impl<'a, W: Write> CountingWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        Self { inner, bytes: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,301 @@
+use chrono::Local;
+use serde_json::json;
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// How collected files are rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `# File:` headers followed by contents and a separator line.
+    #[default]
+    Text,
+    /// One JSON object per file per line.
+    Ndjson,
+    /// A self-contained HTML5 page with a file index.
+    Html,
+    /// An Emacs Org-mode document with one heading and source block per file.
+    Org,
+    /// A reStructuredText document with one section and code block per file.
+    Rst,
+}
+
+const HTML_STYLE: &str = "\
+body { margin: 0; font-family: sans-serif; display: flex; }
+nav { order: -1; position: sticky; top: 0; height: 100vh; overflow-y: auto; padding: 1em; \
+background: #f4f4f4; border-right: 1px solid #ddd; font-size: 0.9em; }
+nav summary { cursor: pointer; font-weight: bold; }
+nav ul { list-style: none; padding-left: 0.5em; }
+nav a { color: #0550ae; text-decoration: none; }
+main { flex: 1; padding: 1em; min-width: 0; }
+h2 { font-size: 1em; font-family: monospace; border-bottom: 1px solid #ddd; }
+pre { background: #fafafa; border: 1px solid #eee; padding: 0.75em; overflow-x: auto; }
+code { font-family: monospace; color: #24292f; }
+code.language-rs { color: #8a3b12; }
+code.language-py { color: #3572a5; }
+code.language-js, code.language-ts { color: #6e5a00; }
+code.language-c, code.language-h, code.language-cpp { color: #555555; }
+code.language-go { color: #00758f; }
+code.language-toml, code.language-json, code.language-yaml, code.language-yml { color: #116329; }
+.error { color: #cf222e; }
+";
+
+impl OutputFormat {
+    /// Parses a `--format` value.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "text" | "txt" => Some(OutputFormat::Text),
+            "ndjson" | "jsonl" => Some(OutputFormat::Ndjson),
+            "html" => Some(OutputFormat::Html),
+            "org" => Some(OutputFormat::Org),
+            "rst" => Some(OutputFormat::Rst),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn write_ndjson(
+        path: &str,
+        contents: &io::Result<String>,
+        sha256: Option<&str>,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        let mut record = match contents {
+            Ok(contents) => json!({ "path": path, "content": contents }),
+            Err(e) => json!({ "path": path, "error": e.to_string() }),
+        };
+        if let Some(sha256) = sha256 {
+            record["sha256"] = json!(sha256);
+        }
+        writeln!(output, "{}", record)
+    }
+
+    /// Writes the Org document's title, date and author lines.
+    pub(crate) fn write_org_start(output: &mut impl Write) -> io::Result<()> {
+        let author = env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_default();
+        writeln!(output, "#+TITLE: agg-files")?;
+        writeln!(output, "#+DATE: {}", Local::now().format("%Y-%m-%d"))?;
+        writeln!(output, "#+AUTHOR: {}", author)?;
+        writeln!(output)
+    }
+
+    pub(crate) fn write_org_file(
+        path: &Path,
+        display_path: &str,
+        contents: &io::Result<String>,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        writeln!(output, "** {}", display_path)?;
+        match contents {
+            Ok(contents) => {
+                writeln!(output, "#+BEGIN_SRC {}", source_language(path))?;
+                for line in contents.lines() {
+                    // Org reads these as headings or keywords even inside a
+                    // block unless they are escaped with a comma
+                    let trimmed = line.trim_start().trim_start_matches(',');
+                    if trimmed.starts_with('*') || trimmed.starts_with("#+") {
+                        write!(output, ",")?;
+                    }
+                    writeln!(output, "{}", line)?;
+                }
+                writeln!(output, "#+END_SRC")?;
+            }
+            Err(_) => writeln!(output, "Error reading file: {}", display_path)?,
+        }
+        writeln!(output)
+    }
+
+    /// Writes the table of contents that opens the RST document.
+    pub(crate) fn write_rst_start(output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, ".. contents::")?;
+        writeln!(output)
+    }
+
+    pub(crate) fn write_rst_file(
+        path: &Path,
+        display_path: &str,
+        contents: &io::Result<String>,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        // The underline has to be at least as long as the title
+        writeln!(output, "{}", display_path)?;
+        writeln!(output, "{}", "=".repeat(display_path.chars().count()))?;
+        writeln!(output)?;
+        match contents {
+            // A code-block directive without content is a Docutils error
+            Ok(contents) if contents.trim().is_empty() => {}
+            Ok(contents) => {
+                writeln!(output, ".. code-block:: {}", source_language(path))?;
+                writeln!(output)?;
+                for line in contents.lines() {
+                    if line.is_empty() {
+                        writeln!(output)?;
+                    } else {
+                        writeln!(output, "    {}", line)?;
+                    }
+                }
+            }
+            Err(_) => writeln!(output, "Error reading file: {}", display_path)?,
+        }
+        writeln!(output)
+    }
+
+    /// Writes the document head and opens the content column.
+    pub(crate) fn write_html_start(output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, "<!DOCTYPE html>")?;
+        writeln!(output, "<html lang=\"en\">")?;
+        writeln!(output, "<head>")?;
+        writeln!(output, "<meta charset=\"utf-8\">")?;
+        writeln!(output, "<title>agg-files</title>")?;
+        writeln!(output, "<style>\n{}</style>", HTML_STYLE)?;
+        writeln!(output, "</head>")?;
+        writeln!(output, "<body>")?;
+        writeln!(output, "<main>")
+    }
+
+    pub(crate) fn write_html_file(
+        path: &Path,
+        display_path: &str,
+        contents: &io::Result<String>,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "txt".to_string());
+        let path = escape_html(display_path);
+
+        writeln!(output, "<section id=\"{}\">", html_id(display_path))?;
+        writeln!(output, "<h2>{}</h2>", path)?;
+        match contents {
+            Ok(contents) => writeln!(
+                output,
+                "<pre><code class=\"language-{}\">{}</code></pre>",
+                escape_html(&ext),
+                escape_html(contents)
+            )?,
+            Err(_) => writeln!(output, "<p class=\"error\">Error reading file: {}</p>", path)?,
+        }
+        writeln!(output, "</section>")
+    }
+
+    /// Closes the content column and writes the collapsible file index.
+    /// The sidebar comes last so it only lists files that were actually
+    /// written; the stylesheet moves it to the left.
+    pub(crate) fn write_html_end(paths: &[String], output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, "</main>")?;
+        writeln!(output, "<nav>")?;
+        writeln!(output, "<details open>")?;
+        writeln!(output, "<summary>Files ({})</summary>", paths.len())?;
+        writeln!(output, "<ul>")?;
+        for path in paths {
+            writeln!(output, "<li><a href=\"#{}\">{}</a></li>", html_id(path), escape_html(path))?;
+        }
+        writeln!(output, "</ul>")?;
+        writeln!(output, "</details>")?;
+        writeln!(output, "</nav>")?;
+        writeln!(output, "</body>")?;
+        writeln!(output, "</html>")
+    }
+}
+
+/// Compression applied to the whole output stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain, uncompressed output.
+    #[default]
+    None,
+    /// gzip (`--compress gzip`).
+    Gzip,
+    /// Zstandard (`--compress zstd`).
+    Zstd,
+}
+
+impl Compression {
+    /// Parses a `--compress` value.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(Compression::None),
+            "gzip" | "gz" => Some(Compression::Gzip),
+            "zstd" | "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Source block language for `path`, named the way Org and Pygments
+/// expect; unknown extensions are passed through as they are.
+fn source_language(path: &Path) -> String {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "text".to_string());
+    let language = match ext.as_str() {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "rb" => "ruby",
+        "sh" | "bash" => "bash",
+        "md" => "markdown",
+        "yml" => "yaml",
+        "h" => "c",
+        "hpp" | "cc" | "cxx" => "cpp",
+        "kt" => "kotlin",
+        "cs" => "csharp",
+        _ => return ext,
+    };
+    language.to_string()
+}
+
+/// A `Language: <name>` comment in the syntax of `path`'s language, for
+/// `--add-language-comment`; `None` for languages it doesn't know.
+pub(crate) fn language_comment(path: &Path) -> Option<String> {
+    let (name, open, close) = match source_language(path).as_str() {
+        "rust" => ("Rust", "//", ""),
+        "javascript" => ("JavaScript", "//", ""),
+        "typescript" => ("TypeScript", "//", ""),
+        "c" => ("C", "//", ""),
+        "cpp" => ("C++", "//", ""),
+        "csharp" => ("C#", "//", ""),
+        "go" => ("Go", "//", ""),
+        "java" => ("Java", "//", ""),
+        "kotlin" => ("Kotlin", "//", ""),
+        "swift" => ("Swift", "//", ""),
+        "php" => ("PHP", "//", ""),
+        "python" => ("Python", "#", ""),
+        "ruby" => ("Ruby", "#", ""),
+        "bash" => ("Bash", "#", ""),
+        "yaml" => ("YAML", "#", ""),
+        "toml" => ("TOML", "#", ""),
+        "sql" => ("SQL", "--", ""),
+        "lua" => ("Lua", "--", ""),
+        "hs" => ("Haskell", "--", ""),
+        "html" => ("HTML", "<!--", " -->"),
+        "xml" => ("XML", "<!--", " -->"),
+        "markdown" => ("Markdown", "<!--", " -->"),
+        "css" => ("CSS", "/*", " */"),
+        _ => return None,
+    };
+    Some(format!("{} Language: {}{}", open, name, close))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// HTML5 ids may contain anything but whitespace.
+fn html_id(path: &str) -> String {
+    let id: String = path.chars().map(|c| if c.is_whitespace() { '-' } else { c }).collect();
+    escape_html(&format!("file-{}", id))
+}
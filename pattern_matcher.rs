@@ -1,21 +1,134 @@
+use lru::LruCache;
 use regex::Regex;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
 
-pub struct PatternMatcher;
+/// Number of compiled patterns kept unless configured otherwise.
+pub const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+/// Translates the simple glob syntax accepted on the command line
+/// (`*`, `{a,b}`) into regexes matched against file paths.
+pub struct PatternMatcher {
+    case_insensitive: bool,
+    anchored: bool,
+    // Compiled regexes keyed by the raw pattern
+    cache: Mutex<LruCache<String, Regex>>,
+}
+
+impl Default for PatternMatcher {
+    fn default() -> Self {
+        Self::new(false, false)
+    }
+}
 
 impl PatternMatcher {
-    pub fn new() -> Self {
-        Self
+    /// Creates a matcher; the flags correspond to `--icase` and
+    /// `--anchor-patterns`.
+    pub fn new(case_insensitive: bool, anchored: bool) -> Self {
+        Self {
+            case_insensitive,
+            anchored,
+            cache: Mutex::new(LruCache::new(Self::capacity(DEFAULT_CACHE_CAPACITY))),
+        }
+    }
+
+    /// Keeps up to `capacity` compiled patterns (at least one) instead of
+    /// [`DEFAULT_CACHE_CAPACITY`].
+    pub fn with_cache_capacity(self, capacity: usize) -> Self {
+        self.cache.lock().unwrap().resize(Self::capacity(capacity));
+        self
     }
 
-    pub fn glob_to_regex(&self, pattern: &str) -> Regex {
+    fn capacity(capacity: usize) -> NonZeroUsize {
+        NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)
+    }
+
+    /// Whether patterns only match relative to the working directory root.
+    pub fn is_anchored(&self) -> bool {
+        self.anchored
+    }
+
+    /// Compiles `pattern` into a regex that matches paths ending with it.
+    ///
+    /// Anchored patterns must match the whole path relative to the working
+    /// directory instead, and `*` no longer crosses `/`, so `*.rs` only
+    /// matches files in the root (like a gitignore pattern without `/`).
+    ///
+    /// Recently used patterns are served from a cache. Fails if the
+    /// pattern doesn't translate into a valid regex (e.g. `a(b`).
+    pub fn glob_to_regex(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(regex) = cache.get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = self.compile(pattern)?;
+        cache.put(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+
+    fn compile(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        let wildcard = if self.anchored { "[^/]*" } else { ".*" };
         let regex_str = pattern
             .replace(".", "\\.")
-            .replace("*", ".*")
+            .replace("*", wildcard)
             .replace("{", "(")
             .replace("}", ")")
             .replace(",", "|")
             .replace(" ", "");  // Remove spaces
         
-        Regex::new(&format!(".*{}$", regex_str)).unwrap()
+        let flags = if self.case_insensitive { "(?i)" } else { "" };
+        let prefix = if self.anchored { "^" } else { ".*" };
+        Regex::new(&format!("{}{}{}$", flags, prefix, regex_str))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_case_sensitively_by_default() {
+        let regex = PatternMatcher::new(false, false).glob_to_regex("*.RS").unwrap();
+        assert!(!regex.is_match("src/main.rs"));
+        assert!(regex.is_match("src/MAIN.RS"));
+    }
+
+    #[test]
+    fn icase_ignores_case() {
+        let regex = PatternMatcher::new(true, false).glob_to_regex("*.RS").unwrap();
+        assert!(regex.is_match("src/main.rs"));
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        let regex = PatternMatcher::new(false, false).glob_to_regex("*.rs").unwrap();
+        assert!(regex.is_match("main.rs"));
+        assert!(regex.is_match("src/main.rs"));
+    }
+
+    #[test]
+    fn anchored_patterns_match_only_at_root() {
+        let matcher = PatternMatcher::new(false, true);
+        let regex = matcher.glob_to_regex("*.rs").unwrap();
+        assert!(regex.is_match("main.rs"));
+        assert!(!regex.is_match("src/main.rs"));
+
+        let regex = matcher.glob_to_regex("src/*.rs").unwrap();
+        assert!(regex.is_match("src/main.rs"));
+        assert!(!regex.is_match("lib/src/main.rs"));
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used_patterns() {
+        let matcher = PatternMatcher::new(false, false).with_cache_capacity(2);
+        matcher.glob_to_regex("*.rs").unwrap();
+        matcher.glob_to_regex("*.py").unwrap();
+        matcher.glob_to_regex("*.rs").unwrap();
+        matcher.glob_to_regex("*.go").unwrap();
+
+        let cache = matcher.cache.lock().unwrap();
+        assert!(cache.contains("*.rs"));
+        assert!(cache.contains("*.go"));
+        assert!(!cache.contains("*.py"));
     }
 }
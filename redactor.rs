@@ -0,0 +1,35 @@
+use regex::Regex;
+
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    replacement: String,
+}
+
+impl Redactor {
+    pub fn new(patterns: &[String], replacement: &str) -> Result<Self, regex::Error> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Regex::new(p))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            patterns,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn apply(&self, contents: &str) -> String {
+        let mut redacted = contents.to_string();
+        for pattern in &self.patterns {
+            // NoExpand keeps `$` in the replacement text literal
+            redacted = pattern
+                .replace_all(&redacted, regex::NoExpand(&self.replacement))
+                .into_owned();
+        }
+        redacted
+    }
+}
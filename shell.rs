@@ -0,0 +1,14 @@
+use std::process::Command;
+
+/// Builds a command that runs `command` through the platform shell.
+pub(crate) fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
@@ -1,8 +1,14 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use directories::ProjectDirs;
 use std::fs;
+use tracing::warn;
+use walkdir::WalkDir;
 use crate::github_handler::RepoInfo;
 
+/// Written into a cached repository once extraction has finished, so an
+/// interrupted download is not mistaken for a usable cache.
+pub const COMPLETE_MARKER: &str = ".agg-complete";
+
 pub struct TempManager {
     base_dir: PathBuf,
 }
@@ -16,17 +22,24 @@ impl TempManager {
         
         // Create base directory if it doesn't exist
         fs::create_dir_all(&base_dir).unwrap_or_else(|_| {
-            eprintln!("Warning: Failed to create cache directory");
+            warn!("Failed to create cache directory");
         });
 
         Self { base_dir }
     }
 
     pub fn get_repo_path(&self, repo_info: &RepoInfo) -> PathBuf {
+        // Each commit gets its own entry so it never aliases the branch tip
+        let reference = match &repo_info.commit {
+            Some(commit) => format!("{}@{}", repo_info.branch, commit),
+            None => repo_info.branch.clone(),
+        };
+        // GitHub Enterprise hosts can reuse owner and repository names
         let repo_dir = self.base_dir
+            .join(&repo_info.host)
             .join(&repo_info.owner)
             .join(&repo_info.repo)
-            .join(&repo_info.branch);
+            .join(reference);
 
         if let Some(path) = &repo_info.path {
             repo_dir.join(path)
@@ -36,6 +49,34 @@ impl TempManager {
     }
 
     pub fn repo_exists(&self, repo_info: &RepoInfo) -> bool {
-        self.get_repo_path(repo_info).exists()
+        self.is_complete(repo_info)
+    }
+
+    pub fn is_complete(&self, repo_info: &RepoInfo) -> bool {
+        self.get_repo_path(repo_info).join(COMPLETE_MARKER).is_file()
+    }
+
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Sum of the sizes of every file in the cache, in bytes.
+    pub fn total_cache_size(&self) -> u64 {
+        WalkDir::new(&self.base_dir)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Number of cached repositories whose download completed.
+    pub fn cached_repo_count(&self) -> usize {
+        WalkDir::new(&self.base_dir)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file() && entry.file_name() == COMPLETE_MARKER)
+            .count()
     }
 }
@@ -0,0 +1,9 @@
+pub struct TokenEstimator;
+
+impl TokenEstimator {
+    /// Rough token estimate for LLM context budgeting: about four
+    /// characters per token for typical source code and English prose.
+    pub fn estimate(contents: &str) -> usize {
+        contents.chars().count().div_ceil(4)
+    }
+}
@@ -1,28 +1,19 @@
 use std::collections::HashMap;
 use std::env::consts;
+use std::fmt;
 
+/// Package and build information for `--version`.
 pub struct Version;
 
 impl Version {
+    /// Prints the package metadata and build details to stdout.
     pub fn print() {
-        let version = env!("CARGO_PKG_VERSION");
-        let name = env!("CARGO_PKG_NAME");
-        let authors = env!("CARGO_PKG_AUTHORS");
-        let description = env!("CARGO_PKG_DESCRIPTION");
-        let repository = env!("CARGO_PKG_REPOSITORY");
-
-        // Print package information
-        println!("{} v{}", name, version);
-        println!("Authors: {}", authors);
-        println!("Description: {}", description);
-        println!("Repository: {}", repository);
+        println!("{}", Version);
+    }
 
-        // Print build information
-        let build_info = Self::build_info();
-        println!("\nBuild Information:");
-        for (key, value) in build_info {
-            println!("  {}: {}", key, value);
-        }
+    /// The text printed by [`Version::print`].
+    pub fn as_string() -> String {
+        Version.to_string()
     }
 
     fn build_info() -> HashMap<String, String> {
@@ -52,3 +43,28 @@ impl Version {
         info
     }
 }
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = env!("CARGO_PKG_VERSION");
+        let name = env!("CARGO_PKG_NAME");
+        let authors = env!("CARGO_PKG_AUTHORS");
+        let description = env!("CARGO_PKG_DESCRIPTION");
+        let repository = env!("CARGO_PKG_REPOSITORY");
+
+        // Package information
+        writeln!(f, "{} v{}", name, version)?;
+        writeln!(f, "Authors: {}", authors)?;
+        writeln!(f, "Description: {}", description)?;
+        writeln!(f, "Repository: {}", repository)?;
+
+        // Build information, sorted so the output is stable
+        let mut build_info: Vec<_> = Self::build_info().into_iter().collect();
+        build_info.sort();
+        write!(f, "\nBuild Information:")?;
+        for (key, value) in build_info {
+            write!(f, "\n  {}: {}", key, value)?;
+        }
+        Ok(())
+    }
+}